@@ -0,0 +1,260 @@
+// differential fuzzing of the assembunny implementations of all days.
+//
+// Random bounded programs are run with a step limit under every implementation and under
+// a deliberately simple reference interpreter written straight from the puzzle texts.
+// Days only get programs with the instructions they understand: day12 has no tgl and
+// no writes to literals, day23 has no out. All implementations that understand a program
+// run that same program, so checking them together compares them with each other.
+// Counter-examples are minimised before they are reported.
+
+use crate::{parse_program, IValue, Instruction, InstructionSet, RunOutcome};
+
+pub const STEP_LIMIT: usize = 2000;
+
+pub type Regs = [isize; 4];
+
+// registers after at most STEP_LIMIT steps, the number of steps it took and the output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    pub regs: Regs,
+    pub steps: usize,
+    pub output: Vec<isize>
+}
+
+// run returns None if the implementation traps
+#[derive(Clone, Copy)]
+pub struct Implementation {
+    pub name: &'static str,
+    pub instructions: InstructionSet,
+    pub run: fn(&[Instruction], Regs) -> Option<Outcome>
+}
+
+pub fn reference(program: &[Instruction], regs: Regs) -> Outcome {
+    let mut program = program.to_vec();
+    let mut regs = regs;
+    let mut ip: isize = 0;
+    let mut steps = 0;
+    let mut output = Vec::new();
+    while ip >= 0 && (ip as usize) < program.len() && steps < STEP_LIMIT {
+        steps += 1;
+        let value = |val: IValue, regs: &Regs| match val {
+            IValue::Literal(intval) => intval,
+            IValue::Register(index) => regs[index as usize]
+        };
+        match program[ip as usize] {
+            Instruction::Cpy(src, IValue::Register(dest)) => regs[dest as usize] = value(src, &regs),
+            Instruction::Inc(IValue::Register(dest)) => regs[dest as usize] += 1,
+            Instruction::Dec(IValue::Register(dest)) => regs[dest as usize] -= 1,
+            Instruction::Jnz(cond, offset) if value(cond, &regs) != 0 => {
+                ip += value(offset, &regs);
+                continue;
+            },
+            Instruction::Out(val) => output.push(value(val, &regs)),
+            Instruction::Tgl(offset) => {
+                let target = ip + value(offset, &regs);
+                if target >= 0 && (target as usize) < program.len() {
+                    let target = target as usize;
+                    // not Instruction::toggle, the reference is written independently
+                    program[target] = match program[target] {
+                        Instruction::Inc(x) => Instruction::Dec(x),
+                        Instruction::Dec(x) | Instruction::Tgl(x) | Instruction::Out(x) => Instruction::Inc(x),
+                        Instruction::Jnz(x, y) => Instruction::Cpy(x, y),
+                        Instruction::Cpy(x, y) => Instruction::Jnz(x, y)
+                    };
+                }
+            },
+            // invalid instruction, skipped
+            _ => {}
+        }
+        ip += 1;
+    }
+    Outcome { regs, steps, output }
+}
+
+// the budget rule every day's run_with_budget follows: at most budget steps are executed,
+// a program that halts within them has halted. Panics on the first case it gets wrong
+pub fn check_budget(run_with_budget: fn(&[Instruction], Regs, usize) -> RunOutcome<4>) {
+    let countdown = parse_program(&["cpy 3 a", "dec a", "jnz a -1"], 4, InstructionSet::Basic).unwrap();
+    let spin = parse_program(&["jnz 1 0"], 4, InstructionSet::Basic).unwrap();
+    let cases = [
+        (&countdown[..], 100, RunOutcome::Halted([0, 0, 0, 0])),
+        // 1 cpy, then 3 times dec and jnz
+        (&countdown[..], 7, RunOutcome::Halted([0, 0, 0, 0])),
+        (&countdown[..], 6, RunOutcome::BudgetExceeded([0, 0, 0, 0])),
+        (&countdown[..], 3, RunOutcome::BudgetExceeded([2, 0, 0, 0])),
+        (&countdown[..], 0, RunOutcome::BudgetExceeded([0, 0, 0, 0])),
+        (&[][..], 0, RunOutcome::Halted([0, 0, 0, 0])),
+        (&spin[..], usize::MAX, RunOutcome::Looped([0, 0, 0, 0]))
+    ];
+    for (program, budget, expected) in cases {
+        assert_eq!(expected, run_with_budget(program, [0; 4], budget), "budget {} for program {:?}", budget, program);
+    }
+}
+
+// xorshift, good enough for generating test programs and factories and reproducible without a seed file
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed.wrapping_mul(0x9e3779b97f4a7c15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    // inclusive on both ends
+    pub fn range(&mut self, low: isize, high: isize) -> isize {
+        low + (self.next() % (high - low + 1) as u64) as isize
+    }
+}
+
+fn random_register(rng: &mut Rng) -> IValue {
+    IValue::Register(rng.range(0, 3) as u8)
+}
+
+fn random_value(rng: &mut Rng) -> IValue {
+    if rng.range(0, 2) == 0 {
+        IValue::Literal(rng.range(-3, 3))
+    } else {
+        random_register(rng)
+    }
+}
+
+pub fn random_program(rng: &mut Rng, instructions: InstructionSet) -> Vec<Instruction> {
+    let len = rng.range(1, 12);
+    let kinds = match instructions {
+        InstructionSet::Basic => 4,
+        InstructionSet::Toggle => 5,
+        InstructionSet::Output => 6
+    };
+    // without tgl, writes to literals are rejected when parsing
+    let destination = |rng: &mut Rng| if instructions == InstructionSet::Basic {random_register(rng)} else {random_value(rng)};
+    let mut program = Vec::new();
+    while program.len() < len as usize {
+        // now and then the idiom of the puzzles, an add loop, which optimising days may
+        // run in one go and random instructions hardly ever form
+        if rng.range(0, 9) == 0 {
+            let (dest, counter) = (random_register(rng), random_register(rng));
+            program.extend([Instruction::Inc(dest), Instruction::Dec(counter), Instruction::Jnz(counter, IValue::Literal(-2))]);
+            continue;
+        }
+        program.push(match rng.range(0, kinds - 1) {
+            0 => Instruction::Cpy(random_value(rng), destination(rng)),
+            1 => Instruction::Inc(destination(rng)),
+            2 => Instruction::Dec(destination(rng)),
+            3 => Instruction::Jnz(random_value(rng), random_value(rng)),
+            4 => Instruction::Tgl(random_value(rng)),
+            _ => Instruction::Out(random_value(rng))
+        });
+    }
+    program
+}
+
+pub fn random_regs(rng: &mut Rng) -> Regs {
+    [0; 4].map(|_| rng.range(-3, 5))
+}
+
+fn smaller_values(val: IValue) -> Vec<IValue> {
+    match val {
+        IValue::Literal(0) => vec![],
+        IValue::Literal(intval) => vec![IValue::Literal(0), IValue::Literal(intval / 2)],
+        IValue::Register(_) => vec![IValue::Literal(0), IValue::Literal(1)]
+    }
+}
+
+// programs that are one step simpler than the given one, using no other instructions
+fn simplifications(program: &[Instruction]) -> Vec<Vec<Instruction>> {
+    let mut result = Vec::new();
+    for index in 0..program.len() {
+        let mut shorter = program.to_vec();
+        shorter.remove(index);
+        result.push(shorter);
+    }
+    for (index, instruction) in program.iter().enumerate() {
+        let replacements: Vec<Instruction> = match *instruction {
+            Instruction::Cpy(x, y) => smaller_values(x).into_iter().map(|x| Instruction::Cpy(x, y)).collect(),
+            Instruction::Jnz(x, y) => smaller_values(x).into_iter().map(|x| Instruction::Jnz(x, y))
+                .chain(smaller_values(y).into_iter().map(|y| Instruction::Jnz(x, y)))
+                .collect(),
+            Instruction::Tgl(x) => smaller_values(x).into_iter().map(Instruction::Tgl).collect(),
+            Instruction::Out(x) => smaller_values(x).into_iter().map(Instruction::Out).collect(),
+            _ => vec![]
+        };
+        for replacement in replacements {
+            let mut simpler = program.to_vec();
+            simpler[index] = replacement;
+            result.push(simpler);
+        }
+    }
+    result
+}
+
+// greedily shrinks a failing case as long as it keeps failing
+pub fn minimise<F>(program: &[Instruction], regs: Regs, fails: F) -> (Vec<Instruction>, Regs)
+where F: Fn(&[Instruction], Regs) -> bool {
+    let mut program = program.to_vec();
+    let mut regs = regs;
+    'shrink: loop {
+        for simpler in simplifications(&program) {
+            if fails(&simpler, regs) {
+                program = simpler;
+                continue 'shrink;
+            }
+        }
+        for index in 0..regs.len() {
+            for value in [0, regs[index] / 2] {
+                if value != regs[index] {
+                    let mut simpler = regs;
+                    simpler[index] = value;
+                    if fails(&program, simpler) {
+                        regs = simpler;
+                        continue 'shrink;
+                    }
+                }
+            }
+        }
+        return (program, regs);
+    }
+}
+
+fn to_source(program: &[Instruction]) -> String {
+    let value = |val: &IValue| match val {
+        IValue::Literal(intval) => intval.to_string(),
+        IValue::Register(index) => ((b'a' + index) as char).to_string()
+    };
+    program.iter().map(|instruction| match instruction {
+        Instruction::Cpy(x, y) => format!("cpy {} {}", value(x), value(y)),
+        Instruction::Inc(x) => format!("inc {}", value(x)),
+        Instruction::Dec(x) => format!("dec {}", value(x)),
+        Instruction::Jnz(x, y) => format!("jnz {} {}", value(x), value(y)),
+        Instruction::Tgl(x) => format!("tgl {}", value(x)),
+        Instruction::Out(x) => format!("out {}", value(x))
+    }).collect::<Vec<_>>().join("\n")
+}
+
+// runs num_cases random programs for every instruction set the implementations understand,
+// panics with a minimised counter-example on the first mismatch
+pub fn check(implementations: &[Implementation], seed: u64, num_cases: usize) {
+    let mut sets = implementations.iter().map(|implementation| implementation.instructions).collect::<Vec<_>>();
+    sets.sort();
+    sets.dedup();
+    let mut rng = Rng::new(seed);
+    for _ in 0..num_cases {
+        for set in sets.iter() {
+            let program = random_program(&mut rng, *set);
+            let regs = random_regs(&mut rng);
+            for implementation in implementations.iter().filter(|implementation| implementation.instructions >= *set) {
+                let fails = |program: &[Instruction], regs: Regs| (implementation.run)(program, regs) != Some(reference(program, regs));
+                if fails(&program, regs) {
+                    let (program, regs) = minimise(&program, regs, fails);
+                    panic!("{} disagrees with the reference for regs {:?} and program\n{}\nexpected {:?}, got {:?}",
+                        implementation.name, regs, to_source(&program), reference(&program, regs), (implementation.run)(&program, regs));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,332 @@
+// what the assembunny interpreters of day12, day23 and day25 share.
+//
+// The instructions and their parser are the same for all days, every day only accepts
+// the ones its puzzle knows (see InstructionSet). The interpreters differ, each day has
+// its own, and the fuzzer compares them.
+
+pub mod fuzz;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IValue {
+    Register(u8),
+    Literal(isize)
+}
+
+impl IValue {
+    pub fn unwrap(&self) -> isize {
+        match self {
+            IValue::Literal(intval) => *intval,
+            IValue::Register(index) => *index as isize
+        }
+    }
+
+    pub fn is_literal(&self) -> bool {
+        matches!(self, IValue::Literal(_))
+    }
+
+    pub fn is_register(&self) -> bool {
+        matches!(self, IValue::Register(_))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Instruction {
+    Cpy(IValue, IValue),
+    Inc(IValue),
+    Dec(IValue),
+    Jnz(IValue, IValue),
+    Tgl(IValue),
+    Out(IValue)
+}
+
+// the instructions a day understands, each set contains the ones before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum InstructionSet {
+    // day12: cpy, inc, dec and jnz, only writing to registers
+    Basic,
+    // day23: adds tgl, which can turn instructions into ones writing to literals
+    Toggle,
+    // day25: adds out
+    Output
+}
+
+// register count of the puzzles' machine
+pub const NUM_REGISTERS: usize = 4;
+
+impl Instruction {
+    // parses a single instruction of the given set for a machine with registers a, b, ...
+    // up to num_registers. With tgl, instructions writing to a literal are accepted,
+    // as tgl may turn them into valid ones
+    pub fn parse(line: &str, num_registers: usize, instructions: InstructionSet) -> Result<Instruction, String> {
+        if !(1..=26).contains(&num_registers) {
+            return Err(format!("machines have 1 to 26 registers, not {}", num_registers));
+        }
+        let (name, args) = line.split_once(' ').ok_or(format!("missing arguments in '{}'", line))?;
+        let arg_to_value = |s: &str| -> Result<IValue, String> {
+            if s.chars().all(|c| c == '-' || c.is_numeric()) {
+                s.parse().map(IValue::Literal).map_err(|_| format!("invalid literal '{}'", s))
+            } else {
+                match s.as_bytes() {
+                    [reg @ b'a'..=b'z'] if ((reg - b'a') as usize) < num_registers => Ok(IValue::Register(reg - b'a')),
+                    _ => Err(format!("unknown register '{}', expected a to {}", s, (b'a' + num_registers as u8 - 1) as char))
+                }
+            }
+        };
+        let args = args.split_whitespace().map(arg_to_value).collect::<Result<Vec<_>, _>>()?;
+        let expect_args = |num: usize| -> Result<(), String> {
+            if args.len() == num {
+                Ok(())
+            } else {
+                Err(format!("'{}' expects {} argument(s), got {}", name, num, args.len()))
+            }
+        };
+        // without tgl, instructions writing to a literal can never become valid
+        let expect_register = |val: IValue| -> Result<IValue, String> {
+            match val {
+                IValue::Literal(intval) if instructions == InstructionSet::Basic => Err(format!("'{}' cannot write to literal {}", name, intval)),
+                _ => Ok(val)
+            }
+        };
+        let expect_set = |needed: InstructionSet| -> Result<(), String> {
+            if instructions >= needed {
+                Ok(())
+            } else {
+                Err(format!("unknown instruction '{}'", name))
+            }
+        };
+        match name.to_lowercase().as_str() {
+            "cpy" => {expect_args(2)?; Ok(Instruction::Cpy(args[0], expect_register(args[1])?))},
+            "inc" => {expect_args(1)?; Ok(Instruction::Inc(expect_register(args[0])?))},
+            "dec" => {expect_args(1)?; Ok(Instruction::Dec(expect_register(args[0])?))},
+            "jnz" => {expect_args(2)?; Ok(Instruction::Jnz(args[0], args[1]))},
+            "tgl" => {expect_set(InstructionSet::Toggle)?; expect_args(1)?; Ok(Instruction::Tgl(args[0]))},
+            "out" => {expect_set(InstructionSet::Output)?; expect_args(1)?; Ok(Instruction::Out(args[0]))},
+            _ => Err(format!("unknown instruction '{}'", name))
+        }
+    }
+
+    // as defined by the puzzle: inc becomes dec and every other one-argument instruction
+    // becomes inc, jnz becomes cpy and every other two-argument instruction becomes jnz.
+    // Toggling an already toggled instruction applies the same rule again,
+    // the result may be invalid (e.g. cpy 1 2) and is then skipped at execution time
+    pub fn toggle(&self) -> Instruction {
+        match *self {
+            Instruction::Inc(val) => Instruction::Dec(val),
+            Instruction::Dec(val) | Instruction::Tgl(val) | Instruction::Out(val) => Instruction::Inc(val),
+            Instruction::Cpy(val1, val2) => Instruction::Jnz(val1, val2),
+            Instruction::Jnz(val1, val2) => Instruction::Cpy(val1, val2)
+        }
+    }
+}
+
+pub fn parse_program(lines: &[&str], num_registers: usize, instructions: InstructionSet) -> Result<Vec<Instruction>, String> {
+    lines.iter()
+        .enumerate()
+        .map(|(lineno, line)| Instruction::parse(line, num_registers, instructions).map_err(|err| format!("line {}: {}", lineno + 1, err)))
+        .collect()
+}
+
+// raised by checked execution instead of silently wrapping around
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Overflow {
+    pub ip: usize,
+    pub instruction: Instruction
+}
+
+impl std::fmt::Display for Overflow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "arithmetic overflow at ip {} ({:?})", self.ip, self.instruction)
+    }
+}
+
+pub fn add(lhs: isize, rhs: isize, checked: bool) -> Option<isize> {
+    if checked {lhs.checked_add(rhs)} else {Some(lhs + rhs)}
+}
+
+// how a run with a step budget ended, with the registers at that point.
+// A run executes at most budget steps, a program that halts within them has halted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunOutcome<const N: usize> {
+    Halted([isize; N]),
+    // an earlier state repeated exactly, so the program never halts
+    Looped([isize; N]),
+    BudgetExceeded([isize; N])
+}
+
+// Brent's cycle detection on the states of the machine. Only a single state is kept,
+// which is replaced after 1, 2, 4, 8, ... steps, so a loop is found at the latest
+// after about twice the number of steps until it closes
+pub struct LoopDetector<I, const N: usize> {
+    saved: Option<(usize, [isize; N], Vec<I>)>,
+    power: usize,
+    since_save: usize
+}
+
+impl<I: Clone + PartialEq, const N: usize> LoopDetector<I, N> {
+    pub fn new() -> LoopDetector<I, N> {
+        LoopDetector { saved: None, power: 1, since_save: 0 }
+    }
+
+    // feed the state after every step, true once it repeats.
+    // The program is part of the state since tgl modifies it
+    pub fn looped(&mut self, ip: usize, regs: &[isize; N], program: &[I]) -> bool {
+        if let Some((saved_ip, saved_regs, saved_program)) = &self.saved {
+            if *saved_ip == ip && saved_regs == regs && saved_program == program {
+                return true;
+            }
+        }
+        self.since_save += 1;
+        if self.since_save >= self.power {
+            self.saved = Some((ip, *regs, program.to_vec()));
+            self.power *= 2;
+            self.since_save = 0;
+        }
+        false
+    }
+}
+
+impl<I: Clone + PartialEq, const N: usize> Default for LoopDetector<I, N> {
+    fn default() -> LoopDetector<I, N> {
+        LoopDetector::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fuzz::Outcome;
+
+    #[test]
+    fn test_parse_validates_registers() {
+        assert_eq!(Ok(Instruction::Inc(IValue::Register(3))), Instruction::parse("inc d", 4, InstructionSet::Basic));
+        assert!(Instruction::parse("inc e", 4, InstructionSet::Basic).unwrap_err().contains("unknown register 'e'"));
+        assert!(Instruction::parse("cpy 1 ab", 4, InstructionSet::Basic).is_err());
+        assert_eq!(Ok(Instruction::Inc(IValue::Register(4))), Instruction::parse("inc e", 5, InstructionSet::Basic));
+        assert_eq!("line 2: unknown register 'e', expected a to d", parse_program(&["inc a", "inc e"], 4, InstructionSet::Basic).unwrap_err());
+    }
+
+    #[test]
+    fn test_parse_validates_arguments() {
+        assert_eq!(Ok(Instruction::Jnz(IValue::Literal(-2), IValue::Register(1))), Instruction::parse("jnz -2 b", 4, InstructionSet::Basic));
+        assert!(Instruction::parse("jnz a", 4, InstructionSet::Basic).unwrap_err().contains("expects 2"));
+        assert!(Instruction::parse("tgl a b", 4, InstructionSet::Toggle).unwrap_err().contains("expects 1"));
+        assert!(Instruction::parse("out", 4, InstructionSet::Output).unwrap_err().contains("missing arguments"));
+        assert!(Instruction::parse("cpy 1x a", 4, InstructionSet::Basic).is_err());
+        assert!(Instruction::parse("mul a b", 4, InstructionSet::Output).unwrap_err().contains("unknown instruction"));
+    }
+
+    #[test]
+    fn test_parse_rejects_instructions_outside_the_set() {
+        assert!(Instruction::parse("tgl a", 4, InstructionSet::Basic).unwrap_err().contains("unknown instruction 'tgl'"));
+        assert!(Instruction::parse("out a", 4, InstructionSet::Toggle).unwrap_err().contains("unknown instruction 'out'"));
+        // only tgl can make writing to a literal valid
+        assert!(Instruction::parse("cpy 1 2", 4, InstructionSet::Basic).unwrap_err().contains("literal"));
+        assert!(Instruction::parse("cpy 1 2", 4, InstructionSet::Toggle).is_ok());
+    }
+
+    #[test]
+    fn test_parse_rejects_register_counts() {
+        assert!(Instruction::parse("inc a", 0, InstructionSet::Basic).unwrap_err().contains("1 to 26 registers"));
+        assert!(Instruction::parse("inc a", 27, InstructionSet::Basic).is_err());
+        assert_eq!(Ok(Instruction::Inc(IValue::Register(25))), Instruction::parse("inc z", 26, InstructionSet::Basic));
+    }
+
+    // (instruction, toggled once, toggled twice)
+    static TOGGLE_TABLE: &[(&str, &str, &str)] = &[
+        ("inc a", "dec a", "inc a"),
+        ("dec a", "inc a", "dec a"),
+        ("tgl a", "inc a", "dec a"),
+        ("out a", "inc a", "dec a"),
+        ("out 1", "inc 1", "dec 1"),
+        ("cpy a b", "jnz a b", "cpy a b"),
+        ("cpy 1 b", "jnz 1 b", "cpy 1 b"),
+        ("jnz a b", "cpy a b", "jnz a b"),
+        ("jnz 1 -2", "cpy 1 -2", "jnz 1 -2"),
+        // invalid instructions are toggled just like valid ones
+        ("cpy 1 2", "jnz 1 2", "cpy 1 2"),
+        ("inc 1", "dec 1", "inc 1"),
+        ("tgl 1", "inc 1", "dec 1"),
+    ];
+
+    #[test]
+    fn test_toggle_conformance() {
+        let parse = |line: &str| Instruction::parse(line, NUM_REGISTERS, InstructionSet::Output).unwrap();
+        for (source, once, twice) in TOGGLE_TABLE {
+            let toggled = parse(source).toggle();
+            assert_eq!(parse(once), toggled, "toggling {}", source);
+            assert_eq!(parse(twice), toggled.toggle(), "toggling {} twice", source);
+        }
+    }
+
+    #[test]
+    fn test_check_budget_rejects_ignored_budgets() {
+        // runs up to the fuzzer's step limit whatever the budget
+        fn broken(program: &[Instruction], regs: fuzz::Regs, _budget: usize) -> RunOutcome<4> {
+            RunOutcome::Halted(fuzz::reference(program, regs).regs)
+        }
+        assert!(std::panic::catch_unwind(|| fuzz::check_budget(broken)).is_err());
+    }
+
+    #[test]
+    fn test_fuzz_minimises_counter_examples() {
+        // an interpreter that swallows its output
+        fn broken(program: &[Instruction], regs: fuzz::Regs) -> Option<Outcome> {
+            let program: Vec<Instruction> = program.iter()
+                .map(|i| if let Instruction::Out(_) = i {Instruction::Jnz(IValue::Literal(0), IValue::Literal(0))} else {*i})
+                .collect();
+            Some(fuzz::reference(&program, regs))
+        }
+        let fails = |program: &[Instruction], regs: fuzz::Regs| broken(program, regs) != Some(fuzz::reference(program, regs));
+        let mut rng = fuzz::Rng::new(1);
+        let (program, regs) = std::iter::repeat_with(|| (fuzz::random_program(&mut rng, InstructionSet::Output), fuzz::random_regs(&mut rng)))
+            .find(|(program, regs)| fails(program, *regs))
+            .unwrap();
+        let (program, regs) = fuzz::minimise(&program, regs, fails);
+        assert_eq!(vec![Instruction::Out(IValue::Literal(0))], program);
+        assert_eq!([0isize; 4], regs);
+    }
+
+    #[test]
+    fn test_fuzz_minimises_toggles() {
+        // an interpreter that forgot about tgl
+        fn broken(program: &[Instruction], regs: fuzz::Regs) -> Option<Outcome> {
+            let program: Vec<Instruction> = program.iter()
+                .map(|i| if let Instruction::Tgl(_) = i {Instruction::Jnz(IValue::Literal(0), IValue::Literal(0))} else {*i})
+                .collect();
+            Some(fuzz::reference(&program, regs))
+        }
+        let fails = |program: &[Instruction], regs: fuzz::Regs| broken(program, regs) != Some(fuzz::reference(program, regs));
+        let mut rng = fuzz::Rng::new(1);
+        let (program, regs) = std::iter::repeat_with(|| (fuzz::random_program(&mut rng, InstructionSet::Toggle), fuzz::random_regs(&mut rng)))
+            .find(|(program, regs)| fails(program, *regs))
+            .unwrap();
+        let (program, regs) = fuzz::minimise(&program, regs, fails);
+        assert!(fails(&program, regs));
+        // a tgl and the instruction it toggles, everything else is noise
+        assert_eq!(2, program.len(), "{:?}", program);
+        assert!(program.iter().any(|i| matches!(i, Instruction::Tgl(_))));
+    }
+
+    #[test]
+    fn test_fuzz_basic_programs_write_registers_only() {
+        let mut rng = fuzz::Rng::new(2);
+        for _ in 0..1000 {
+            for instruction in fuzz::random_program(&mut rng, InstructionSet::Basic) {
+                match instruction {
+                    Instruction::Cpy(_, dest) | Instruction::Inc(dest) | Instruction::Dec(dest) => assert!(matches!(dest, IValue::Register(_))),
+                    Instruction::Jnz(_, _) => {},
+                    other => panic!("{:?} is not a basic instruction", other)
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_loop_detector() {
+        let mut detector = LoopDetector::<u8, 1>::new();
+        assert!(!detector.looped(0, &[0], &[1]));
+        assert!(!detector.looped(1, &[0], &[1]));
+        assert!(!detector.looped(0, &[0], &[2]));
+        assert!(detector.looped(0, &[0], &[2]));
+    }
+}
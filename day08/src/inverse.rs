@@ -0,0 +1,107 @@
+// generates puzzle inputs: a sequence of rect and rotate operations that draws a given image.
+//
+// The image is built column by column, starting with the rightmost one. Each column is drawn
+// into column 0 with rect 1x1 and rotations of that column, then every row rotates right
+// to make room for the next one, like a conveyor belt. Column 0 is always empty before it
+// gets drawn, since nothing has wrapped around yet. Rotating rows that are still empty
+// does nothing, so those are left out, and consecutive rotations are merged.
+
+use crate::{ocr, Screen, ScreenOp};
+
+// an image drawn with '#' for lit pixels, anything else is dark
+pub fn parse_image(text: &str) -> Screen {
+    let lines = text.lines().collect::<Vec<_>>();
+    let width = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0);
+    let mut screen = Screen::new(width, lines.len());
+    for (yi, line) in lines.iter().enumerate() {
+        for (xi, pixel) in line.chars().enumerate() {
+            if pixel == '#' {
+                screen.pixels[yi * width + xi] = 1;
+            }
+        }
+    }
+    screen
+}
+
+// the text in the puzzle font, on a screen at least min_width wide
+pub fn banner(text: &str, min_width: usize) -> Result<Screen, String> {
+    let width = (text.chars().count() * ocr::GLYPH_WIDTH).max(min_width);
+    let mut screen = Screen::new(width, ocr::GLYPH_HEIGHT);
+    for (index, letter) in text.chars().enumerate() {
+        let (_, glyph) = ocr::FONT.iter()
+            .find(|(known, _)| *known == letter.to_ascii_uppercase())
+            .ok_or(format!("no glyph for '{}' in the font", letter))?;
+        for (yi, row) in glyph.iter().enumerate() {
+            for (xi, pixel) in row.chars().enumerate() {
+                if pixel == '#' {
+                    screen.pixels[yi * screen.width + index * ocr::GLYPH_WIDTH + xi] = 1;
+                }
+            }
+        }
+    }
+    Ok(screen)
+}
+
+// draws one column into the empty column 0, from the bottom up: a pixel lit at row 0
+// while handling row yi is rotated down exactly yi times afterwards
+fn draw_column(column: &[bool], ops: &mut Vec<ScreenOp>) {
+    let mut pending = 0;
+    let mut drawn = false;
+    for yi in (0..column.len()).rev() {
+        if column[yi] {
+            if drawn && pending > 0 {
+                ops.push(ScreenOp::RotateColumn(0, pending));
+            }
+            ops.push(ScreenOp::Rect(1, 1));
+            drawn = true;
+            pending = 0;
+        }
+        if yi > 0 {
+            pending += 1;
+        }
+    }
+    if drawn && pending > 0 {
+        ops.push(ScreenOp::RotateColumn(0, pending));
+    }
+}
+
+pub fn generate(target: &Screen) -> Vec<ScreenOp> {
+    let mut ops = Vec::new();
+    // rows with at least one lit pixel so far, only those need to be rotated
+    let mut used_rows = vec![false; target.height];
+    // rotation of all rows still to be done
+    let mut pending = 0;
+    for xi in (0..target.width).rev() {
+        let column = (0..target.height).map(|yi| target.pixels[yi * target.width + xi] != 0).collect::<Vec<_>>();
+        if column.contains(&true) {
+            if pending > 0 {
+                for yi in (0..target.height).filter(|yi| used_rows[*yi]) {
+                    ops.push(ScreenOp::RotateRow(yi, pending));
+                }
+                pending = 0;
+            }
+            draw_column(&column, &mut ops);
+            for (used, lit) in used_rows.iter_mut().zip(&column) {
+                *used |= *lit;
+            }
+        }
+        if xi > 0 {
+            pending += 1;
+        }
+    }
+    for yi in (0..target.height).filter(|yi| used_rows[*yi]) {
+        if pending > 0 {
+            ops.push(ScreenOp::RotateRow(yi, pending));
+        }
+    }
+    ops
+}
+
+// replays the ops on an empty screen of the same size
+pub fn verify(ops: &[ScreenOp], target: &Screen) -> bool {
+    let mut screen = Screen::new(target.width, target.height);
+    for op in ops {
+        screen.perform_mut(*op);
+    }
+    screen.pixels == target.pixels
+}
@@ -3,28 +3,61 @@
 
 use std::fmt::Display;
 
+mod inverse;
+mod ocr;
+mod render;
 
-#[derive(Debug, PartialEq, Eq)]
+
+// besides the puzzle's rect and rotate, we understand
+//     clear
+//     rect 3x2 at 4,1          rect with its top left corner somewhere else
+//     invert rect 3x2 [at 4,1]
+//     shift row y=0 by 4       like rotate, but pixels falling off the edge are lost
+//     shift column x=1 by 1
+// rects are clipped to the screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum ScreenOp {
     Rect(usize, usize),
     RotateRow(usize, usize),
-    RotateColumn(usize, usize)
+    RotateColumn(usize, usize),
+    Clear,
+    // width, height, x, y
+    RectAt(usize, usize, usize, usize),
+    InvertRect(usize, usize, usize, usize),
+    ShiftRow(usize, usize),
+    ShiftColumn(usize, usize)
+}
+
+// "AxB" with an optional " at X,Y", as width, height, x, y
+fn parse_rect(spec: &str) -> (usize, usize, usize, usize) {
+    let (size, position) = spec.split_once(" at ").unwrap_or((spec, "0,0"));
+    let (num1, num2) = size.split_once('x').unwrap();
+    let (num3, num4) = position.split_once(',').unwrap();
+    (num1.parse().unwrap(), num2.parse().unwrap(), num3.parse().unwrap(), num4.parse().unwrap())
 }
 
 impl From<&str> for ScreenOp {
     fn from(value: &str) -> Self {
-        if value.starts_with("rect") {
-            let (num1, num2) = &value[5..].split_once('x').unwrap();
-            return ScreenOp::Rect(num1.parse().unwrap(), num2.parse().unwrap());
-        } else if value.starts_with("rotate") {
+        if value == "clear" {
+            ScreenOp::Clear
+        } else if let Some(spec) = value.strip_prefix("invert rect ") {
+            let (width, height, x, y) = parse_rect(spec);
+            ScreenOp::InvertRect(width, height, x, y)
+        } else if value.starts_with("rect") {
+            match parse_rect(&value[5..]) {
+                (width, height, 0, 0) => ScreenOp::Rect(width, height),
+                (width, height, x, y) => ScreenOp::RectAt(width, height, x, y)
+            }
+        } else if value.starts_with("rotate") || value.starts_with("shift") {
             let (_, nums) = value.split_once("=").unwrap();
             let (num1, num2) = nums.split_once(" by ").unwrap();
             let num1 = num1.parse().unwrap();
             let num2 = num2.parse().unwrap();
-            if value.contains("row") {
-                return ScreenOp::RotateRow(num1, num2);
-            } else {
-                return ScreenOp::RotateColumn(num1, num2);
+            match (value.starts_with("rotate"), value.contains("row")) {
+                (true, true) => ScreenOp::RotateRow(num1, num2),
+                (true, false) => ScreenOp::RotateColumn(num1, num2),
+                (false, true) => ScreenOp::ShiftRow(num1, num2),
+                (false, false) => ScreenOp::ShiftColumn(num1, num2)
             }
         } else {
             panic!("Unknown operation")
@@ -32,6 +65,22 @@ impl From<&str> for ScreenOp {
     }
 }
 
+// in the input syntax
+impl Display for ScreenOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScreenOp::Rect(width, height) => write!(f, "rect {}x{}", width, height),
+            ScreenOp::RotateRow(y, amount) => write!(f, "rotate row y={} by {}", y, amount),
+            ScreenOp::RotateColumn(x, amount) => write!(f, "rotate column x={} by {}", x, amount),
+            ScreenOp::Clear => write!(f, "clear"),
+            ScreenOp::RectAt(width, height, x, y) => write!(f, "rect {}x{} at {},{}", width, height, x, y),
+            ScreenOp::InvertRect(width, height, x, y) => write!(f, "invert rect {}x{} at {},{}", width, height, x, y),
+            ScreenOp::ShiftRow(y, amount) => write!(f, "shift row y={} by {}", y, amount),
+            ScreenOp::ShiftColumn(x, amount) => write!(f, "shift column x={} by {}", x, amount)
+        }
+    }
+}
+
 struct Screen {
     pixels: Vec<i8>,
     width: usize,
@@ -63,13 +112,30 @@ impl Screen {
         Screen {pixels, width, height}
     }
 
+    // applies change to every pixel of the rect, as far as it is on the screen
+    fn update_rect<F: Fn(i8) -> i8>(&mut self, width: usize, height: usize, x: usize, y: usize, change: F) {
+        for yi in y..(y + height).min(self.height) {
+            for xi in x..(x + width).min(self.width) {
+                let pixel = &mut self.pixels[yi * self.width + xi];
+                *pixel = change(*pixel);
+            }
+        }
+    }
+
     fn perform_mut(&mut self, op: ScreenOp) {
         match op {
-            ScreenOp::Rect(x, y) => {
-                for yi in 0..y {
-                    for xi in 0..x {
-                        self.pixels[yi * self.width + xi] = 1;
-                    }
+            ScreenOp::Rect(width, height) => self.update_rect(width, height, 0, 0, |_| 1),
+            ScreenOp::Clear => self.pixels.fill(0),
+            ScreenOp::RectAt(width, height, x, y) => self.update_rect(width, height, x, y, |_| 1),
+            ScreenOp::InvertRect(width, height, x, y) => self.update_rect(width, height, x, y, |pixel| 1 - pixel),
+            ScreenOp::ShiftColumn(x, amount) => {
+                for yi in (0..self.height).rev() {
+                    self.pixels[yi * self.width + x] = if yi >= amount {self.pixels[(yi - amount) * self.width + x]} else {0};
+                }
+            },
+            ScreenOp::ShiftRow(y, amount) => {
+                for xi in (0..self.width).rev() {
+                    self.pixels[y * self.width + xi] = if xi >= amount {self.pixels[y * self.width + xi - amount]} else {0};
                 }
             },
             ScreenOp::RotateColumn(x, amount) => {
@@ -91,25 +157,115 @@ impl Screen {
                     let sourcepos = (xi + self.width - amount) % self.width;
                     self.pixels[y * self.width + xi] = original[sourcepos];
                 }
-            },
-            _ => panic!("Untreated op")
+            }
         }
     }
 }
 
-fn part1(lines: &Vec<&str>) -> Option<usize> {
-    let mut screen = Screen::new(50, 6);
+// the puzzle's display, unless the input starts with a "size WxH" directive
+const DEFAULT_SIZE: (usize, usize) = (50, 6);
+
+// the display size and the lines with the operations
+fn split_size<'a>(lines: &'a [&'a str]) -> ((usize, usize), &'a [&'a str]) {
+    match lines.first().and_then(|line| line.strip_prefix("size ")) {
+        Some(size) => {
+            let (width, height) = size.split_once('x').expect("Size must be given as WxH");
+            let size = (width.parse().expect("Invalid width"), height.parse().expect("Invalid height"));
+            (size, &lines[1..])
+        },
+        None => (DEFAULT_SIZE, lines)
+    }
+}
+
+fn draw(lines: &Vec<&str>) -> Screen {
+    let ((width, height), lines) = split_size(lines);
+    let mut screen = Screen::new(width, height);
     for line in lines {
         let op = ScreenOp::from(*line);
         screen.perform_mut(op);
     }
-    println!("{}", screen);
-    Some(screen.pixels.iter().filter(|p| **p != 0).count())
+    screen
 }
 
-fn part2(lines: &Vec<&str>) -> Option<usize> {
-    // see console output for part 1
-    None
+fn part1(lines: &Vec<&str>) -> Option<String> {
+    let screen = draw(lines);
+    Some(screen.pixels.iter().filter(|p| **p != 0).count().to_string())
+}
+
+fn part2(lines: &Vec<&str>) -> Option<String> {
+    let screen = draw(lines);
+    match ocr::recognise(&screen) {
+        Ok(text) => Some(text),
+        Err(err) => {
+            // show what is on the screen, so it can still be read by eye
+            println!("{}\n{}", err, screen);
+            None
+        }
+    }
+}
+
+fn render_main(args: &[String]) {
+    use std::fs::File;
+    use std::io::BufWriter;
+    let arg = |index: usize| -> &str {
+        args.get(index).map(|a| a.as_str()).unwrap_or_else(|| {
+            println!("Missing argument, run without arguments for usage");
+            std::process::exit(1);
+        })
+    };
+    let read_lines = |path: &str| -> Vec<String> {
+        let contents = std::fs::read_to_string(path).expect("Could not read in file");
+        contents.lines().map(|l| l.to_string()).collect()
+    };
+    let number = |index: usize, default: usize| -> usize {
+        args.get(index).map(|a| a.parse().expect("Expected a number")).unwrap_or(default)
+    };
+    match args[1].as_str() {
+        "--pbm" | "--png" => {
+            let lines = read_lines(arg(3));
+            let screen = draw(&lines.iter().map(|l| l.as_str()).collect());
+            let mut out = BufWriter::new(File::create(arg(2)).expect("Could not create image file"));
+            let scale = number(4, 1);
+            if args[1] == "--pbm" {
+                render::write_pbm(&screen, scale, &mut out)
+            } else {
+                render::write_png(&screen, scale, &mut out)
+            }.expect("Could not write image");
+        },
+        "--animate" => {
+            let lines = read_lines(arg(2));
+            let lines = lines.iter().map(|l| l.as_str()).collect::<Vec<_>>();
+            let ((width, height), lines) = split_size(&lines);
+            let ops = lines.iter().map(|l| ScreenOp::from(*l)).collect::<Vec<_>>();
+            let delay = std::time::Duration::from_millis(number(3, 50) as u64);
+            render::animate(&ops, width, height, delay, &mut std::io::stdout()).expect("Could not write to stdout");
+        },
+        "--inverse" | "--banner" => {
+            let target = if args[1] == "--inverse" {
+                inverse::parse_image(&std::fs::read_to_string(arg(2)).expect("Could not read in file"))
+            } else {
+                inverse::banner(arg(2), DEFAULT_SIZE.0).unwrap_or_else(|err| {
+                    println!("{}", err);
+                    std::process::exit(1);
+                })
+            };
+            let ops = inverse::generate(&target);
+            if !inverse::verify(&ops, &target) {
+                println!("Generated operations do not reproduce the image");
+                std::process::exit(1);
+            }
+            if (target.width, target.height) != DEFAULT_SIZE {
+                println!("size {}x{}", target.width, target.height);
+            }
+            for op in ops {
+                println!("{}", op);
+            }
+        },
+        flag => {
+            println!("Unknown option {}", flag);
+            std::process::exit(1);
+        }
+    }
 }
 
 fn main() {
@@ -117,15 +273,32 @@ fn main() {
     use std::env;
     use std::time::Instant;
     let args: Vec<String> =  env::args().collect();
+    if args.len() > 1 && args[1].starts_with("--") {
+        render_main(&args);
+        return;
+    }
     let infile = args.get(1).unwrap_or_else(|| {
-        println!("Usage: {} <puzzle input>", args[0]);
+        println!("Usage: {} <puzzle input> [WxH]", args[0]);
+        println!("       {} --pbm <image file> <puzzle input> [scale]", args[0]);
+        println!("       {} --png <image file> <puzzle input> [scale]", args[0]);
+        println!("       {} --animate <puzzle input> [delay in ms]", args[0]);
+        println!("       {} --inverse <image file>", args[0]);
+        println!("       {} --banner <text>", args[0]);
         std::process::exit(1);
     });
 
     let contents = fs::read_to_string(infile)
         .expect("Could not read in file");
 
-    let lines: Vec<&str> = contents.lines().collect();
+    let mut lines: Vec<&str> = contents.lines().collect();
+    // a size on the command line replaces the one in the input
+    let size_directive = args.get(2).map(|size| format!("size {}", size));
+    if let Some(directive) = &size_directive {
+        if lines.first().is_some_and(|line| line.starts_with("size ")) {
+            lines.remove(0);
+        }
+        lines.insert(0, directive);
+    }
 
     // execute part 1 and part 2, print their results if they exist
     // later parts may follow, so we loop over the part functions
@@ -148,12 +321,199 @@ mod tests {
     #[test]
     fn test_part1() {
         let lines: Vec<&str> = TESTINPUT.lines().collect();
-        assert_eq!(Some(1337), part1(&lines));
+        assert_eq!(Some("1337".to_string()), part1(&lines));
     }
 
     #[test]
     fn test_part2() {
         let lines: Vec<&str> = TESTINPUT.lines().collect();
-        assert_eq!(Some(13337), part2(&lines));
+        assert_eq!(Some("13337".to_string()), part2(&lines));
+    }
+
+    // a screen showing the given text in the puzzle font
+    fn render(text: &str) -> Screen {
+        inverse::banner(text, 0).unwrap()
+    }
+
+    // every letter of the font, drawn by hand
+    static LETTERS: [(&str, &str); 2] = [
+        ("ABCEFGHIJK", "\
+.##..###...##..####.####..##..#..#..###...##.#..#.
+#..#.#..#.#..#.#....#....#..#.#..#...#.....#.#.#..
+#..#.###..#....###..###..#....####...#.....#.##...
+####.#..#.#....#....#....#.##.#..#...#.....#.#.#..
+#..#.#..#.#..#.#....#....#..#.#..#...#..#..#.#.#..
+#..#.###...##..####.#.....###.#..#..###..##..#..#."),
+        ("LOPRSUYZ", "\
+#.....##..###..###...###.#..#.#...#####.
+#....#..#.#..#.#..#.#....#..#.#...#...#.
+#....#..#.#..#.#..#.#....#..#..#.#...#..
+#....#..#.###..###...##..#..#...#...#...
+#....#..#.#....#.#.....#.#..#...#..#....
+####..##..#....#..#.###...##....#..####.")
+    ];
+
+    #[test]
+    fn test_ocr_reads_every_letter() {
+        for (text, image) in LETTERS {
+            assert_eq!(text, ocr::recognise(&inverse::parse_image(image)).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_ocr_reads_drawn_screen() {
+        // an I, moved to the second cell, and an L drawn next to it
+        let lines = vec!["size 10x6", "rect 1x6",
+            "rotate row y=0 by 1", "rotate row y=1 by 1", "rotate row y=2 by 1",
+            "rotate row y=3 by 1", "rotate row y=4 by 1", "rotate row y=5 by 1",
+            "rect 3x1", "rotate column x=0 by 5", "rotate column x=2 by 5", "rect 3x1",
+            "rotate row y=0 by 6", "rotate row y=1 by 6", "rotate row y=2 by 6",
+            "rotate row y=3 by 6", "rotate row y=4 by 6", "rotate row y=5 by 6",
+            "rect 1x6", "rect 4x1", "rotate column x=1 by 5", "rotate column x=2 by 5", "rotate column x=3 by 5"];
+        assert_eq!("#     ### \n#      #  \n#      #  \n#      #  \n#      #  \n####  ### \n", draw(&lines).to_string());
+        assert_eq!(Some("LI".to_string()), part2(&lines));
+        assert_eq!(Some("19".to_string()), part1(&lines));
+    }
+
+    #[test]
+    fn test_ocr_partial_last_cell() {
+        // the gap after the last letter may be cut off
+        let mut screen = render("ZJ");
+        screen.width -= 1;
+        screen.pixels = screen.pixels.chunks(10).flat_map(|row| row[..9].to_vec()).collect();
+        assert_eq!("ZJ", ocr::recognise(&screen).unwrap());
+    }
+
+    #[test]
+    fn test_pbm_export() {
+        let mut screen = Screen::new(3, 2);
+        screen.perform_mut(ScreenOp::Rect(1, 1));
+        let mut out = Vec::new();
+        render::write_pbm(&screen, 1, &mut out).unwrap();
+        assert_eq!("P1\n3 2\n1 0 0\n0 0 0\n", String::from_utf8(out).unwrap());
+        let mut out = Vec::new();
+        render::write_pbm(&screen, 2, &mut out).unwrap();
+        assert_eq!("P1\n6 4\n1 1 0 0 0 0\n1 1 0 0 0 0\n0 0 0 0 0 0\n0 0 0 0 0 0\n", String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn test_png_export() {
+        let mut screen = Screen::new(3, 2);
+        screen.perform_mut(ScreenOp::Rect(1, 1));
+        let mut out = Vec::new();
+        render::write_png(&screen, 1, &mut out).unwrap();
+        assert!(out.starts_with(b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR\0\0\0\x03\0\0\0\x02"));
+        // IEND has a well known checksum
+        assert!(out.ends_with(b"\0\0\0\0IEND\xae\x42\x60\x82"));
+        // the single stored block holds both rows, each with its filter byte
+        let idat = out.windows(4).position(|w| w == b"IDAT").unwrap() + 4;
+        assert_eq!([0x78, 0x01, 1, 8, 0, 0xf7, 0xff, 0, 0x00, 0xff, 0xff, 0, 0xff, 0xff, 0xff], out[idat..idat + 15]);
+    }
+
+    #[test]
+    fn test_export_rejects_scale_zero() {
+        let screen = Screen::new(3, 2);
+        let mut out = Vec::new();
+        assert_eq!(std::io::ErrorKind::InvalidInput, render::write_pbm(&screen, 0, &mut out).unwrap_err().kind());
+        assert_eq!(std::io::ErrorKind::InvalidInput, render::write_png(&screen, 0, &mut out).unwrap_err().kind());
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn test_animation_frames() {
+        let ops = ["rect 3x2", "rotate column x=1 by 1", "rotate row y=0 by 4"].map(ScreenOp::from);
+        let mut out = Vec::new();
+        render::animate(&ops, 7, 3, std::time::Duration::ZERO, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(3, out.matches("\x1b[H").count());
+        assert!(out.contains("3/3 RotateRow(0, 4)"));
+        // the last frame is the final screen
+        assert!(out.ends_with("        ██  ██\n██████        \n  ██          \n\x1b[?25h"));
+    }
+
+    #[test]
+    fn test_parse_extended_ops() {
+        assert_eq!(ScreenOp::Clear, ScreenOp::from("clear"));
+        assert_eq!(ScreenOp::Rect(3, 2), ScreenOp::from("rect 3x2"));
+        assert_eq!(ScreenOp::Rect(3, 2), ScreenOp::from("rect 3x2 at 0,0"));
+        assert_eq!(ScreenOp::RectAt(3, 2, 4, 1), ScreenOp::from("rect 3x2 at 4,1"));
+        assert_eq!(ScreenOp::InvertRect(3, 2, 0, 0), ScreenOp::from("invert rect 3x2"));
+        assert_eq!(ScreenOp::InvertRect(3, 2, 4, 1), ScreenOp::from("invert rect 3x2 at 4,1"));
+        assert_eq!(ScreenOp::RotateRow(0, 4), ScreenOp::from("rotate row y=0 by 4"));
+        assert_eq!(ScreenOp::ShiftRow(0, 4), ScreenOp::from("shift row y=0 by 4"));
+        assert_eq!(ScreenOp::ShiftColumn(1, 1), ScreenOp::from("shift column x=1 by 1"));
+    }
+
+    #[test]
+    fn test_extended_ops() {
+        let lines = vec!["size 6x3", "rect 2x2 at 3,0", "invert rect 3x3 at 2,1", "shift row y=0 by 2", "shift column x=2 by 1"];
+        assert_eq!("     #\n      \n  ### \n", draw(&lines).to_string());
+        let lines = vec!["size 4x2", "rect 4x2", "clear", "rect 9x9 at 3,1"];
+        assert_eq!("    \n   #\n", draw(&lines).to_string());
+        // a plain rect is clipped as well, it must not wrap into the next row
+        assert_eq!("####\n    \n", draw(&vec!["size 4x2", "rect 5x1"]).to_string());
+        assert_eq!("####\n####\n", draw(&vec!["size 4x2", "rect 9x9 at 0,0"]).to_string());
+    }
+
+    #[test]
+    fn test_size_directive() {
+        assert_eq!((50, 6), (draw(&vec!["rect 1x1"]).width, draw(&vec![]).height));
+        let screen = draw(&vec!["size 7x3", "rect 3x2", "rotate column x=1 by 1", "rotate row y=0 by 4", "rotate column x=1 by 1"]);
+        assert_eq!(" #  # #\n# #    \n #     \n", screen.to_string());
+        assert_eq!(Some("6".to_string()), part1(&vec!["size 7x3", "rect 3x2", "rotate column x=1 by 1"]));
+    }
+
+    #[test]
+    fn test_display_roundtrip() {
+        for line in ["rect 3x2", "rotate row y=0 by 4", "rotate column x=1 by 1", "clear", "rect 3x2 at 4,1",
+                     "invert rect 3x2 at 4,1", "shift row y=0 by 4", "shift column x=1 by 1"] {
+            assert_eq!(line, ScreenOp::from(line).to_string());
+        }
+    }
+
+    #[test]
+    fn test_inverse_banner() {
+        let target = inverse::banner("HELLO", 50).unwrap();
+        let ops = inverse::generate(&target);
+        assert!(inverse::verify(&ops, &target));
+        assert!(ops.iter().all(|op| matches!(op, ScreenOp::Rect(..) | ScreenOp::RotateRow(..) | ScreenOp::RotateColumn(..))));
+        // as a puzzle input, solved by part 2
+        let lines = ops.iter().map(|op| op.to_string()).collect::<Vec<_>>();
+        assert_eq!(Some("HELLO".to_string()), part2(&lines.iter().map(|l| l.as_str()).collect()));
+        assert!(inverse::banner("hi!", 50).is_err());
+    }
+
+    #[test]
+    fn test_inverse_images() {
+        let target = inverse::parse_image("#..#\n....\n.##.");
+        assert!(inverse::verify(&inverse::generate(&target), &target));
+        // every pixel of a full screen, and a blank one
+        let full = inverse::parse_image(&"#####\n".repeat(4));
+        assert!(inverse::verify(&inverse::generate(&full), &full));
+        assert!(inverse::generate(&Screen::new(5, 4)).is_empty());
+        // pseudo random images
+        let mut seed = 0x2545f491u32;
+        for _ in 0..200 {
+            let mut target = Screen::new(9, 5);
+            for pixel in target.pixels.iter_mut() {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                *pixel = seed.is_multiple_of(3) as i8;
+            }
+            assert!(inverse::verify(&inverse::generate(&target), &target));
+        }
+    }
+
+    #[test]
+    fn test_ocr_unknown_glyph() {
+        let mut screen = render("AB");
+        screen.pixels[8] = 1;
+        let err = ocr::recognise(&screen).unwrap_err();
+        assert!(matches!(err, ocr::OcrError::UnknownGlyph { index: 1, .. }));
+        assert!(err.to_string().starts_with("unknown glyph at letter 1 (columns 5..10):\n####.\n#..#."));
+        assert_eq!(Err(ocr::OcrError::WrongHeight(3)), ocr::recognise(&Screen::new(5, 3)));
+        // a filled cell is no letter, part 2 has no result then
+        assert_eq!(None, part2(&vec!["size 10x6", "rect 4x6"]));
     }
 }
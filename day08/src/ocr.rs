@@ -0,0 +1,81 @@
+// reads the letters drawn on a Screen.
+//
+// The puzzle uses the usual AoC font: glyphs are 6 pixels high and 4 wide (Y is 5),
+// every letter sits in a cell of 5 columns, the last one normally being the gap to the next.
+
+use crate::Screen;
+
+pub const GLYPH_WIDTH: usize = 5;
+pub const GLYPH_HEIGHT: usize = 6;
+
+// every known glyph as its full 5x6 cell
+pub const FONT: &[(char, [&str; GLYPH_HEIGHT])] = &[
+    ('A', [".##..", "#..#.", "#..#.", "####.", "#..#.", "#..#."]),
+    ('B', ["###..", "#..#.", "###..", "#..#.", "#..#.", "###.."]),
+    ('C', [".##..", "#..#.", "#....", "#....", "#..#.", ".##.."]),
+    ('E', ["####.", "#....", "###..", "#....", "#....", "####."]),
+    ('F', ["####.", "#....", "###..", "#....", "#....", "#...."]),
+    ('G', [".##..", "#..#.", "#....", "#.##.", "#..#.", ".###."]),
+    ('H', ["#..#.", "#..#.", "####.", "#..#.", "#..#.", "#..#."]),
+    ('I', [".###.", "..#..", "..#..", "..#..", "..#..", ".###."]),
+    ('J', ["..##.", "...#.", "...#.", "...#.", "#..#.", ".##.."]),
+    ('K', ["#..#.", "#.#..", "##...", "#.#..", "#.#..", "#..#."]),
+    ('L', ["#....", "#....", "#....", "#....", "#....", "####."]),
+    ('O', [".##..", "#..#.", "#..#.", "#..#.", "#..#.", ".##.."]),
+    ('P', ["###..", "#..#.", "#..#.", "###..", "#....", "#...."]),
+    ('R', ["###..", "#..#.", "#..#.", "###..", "#.#..", "#..#."]),
+    ('S', [".###.", "#....", "#....", ".##..", "...#.", "###.."]),
+    ('U', ["#..#.", "#..#.", "#..#.", "#..#.", "#..#.", ".##.."]),
+    ('Y', ["#...#", "#...#", ".#.#.", "..#..", "..#..", "..#.."]),
+    ('Z', ["####.", "...#.", "..#..", ".#...", "#....", "####."]),
+    (' ', [".....", ".....", ".....", ".....", ".....", "....."])
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OcrError {
+    // the font is exactly GLYPH_HEIGHT pixels high
+    WrongHeight(usize),
+    // the cell at this index matches no glyph of the font, pattern as in FONT
+    UnknownGlyph { index: usize, pattern: Vec<String> }
+}
+
+impl std::fmt::Display for OcrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OcrError::WrongHeight(height) => {
+                write!(f, "screen is {} pixels high, the font needs {}", height, GLYPH_HEIGHT)
+            },
+            OcrError::UnknownGlyph { index, pattern } => {
+                write!(f, "unknown glyph at letter {} (columns {}..{}):", index, index * GLYPH_WIDTH, (index + 1) * GLYPH_WIDTH)?;
+                for row in pattern {
+                    write!(f, "\n{}", row)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// the cell of the given letter, columns beyond the right edge of the screen are dark
+fn cell(screen: &Screen, index: usize) -> Vec<String> {
+    (0..screen.height).map(|yi| {
+        (index * GLYPH_WIDTH..(index + 1) * GLYPH_WIDTH)
+            .map(|xi| if xi < screen.width && screen.pixels[yi * screen.width + xi] != 0 {'#'} else {'.'})
+            .collect()
+    }).collect()
+}
+
+pub fn recognise(screen: &Screen) -> Result<String, OcrError> {
+    if screen.height != GLYPH_HEIGHT {
+        return Err(OcrError::WrongHeight(screen.height));
+    }
+    let num_cells = screen.width.div_ceil(GLYPH_WIDTH);
+    let text = (0..num_cells).map(|index| {
+        let pattern = cell(screen, index);
+        FONT.iter()
+            .find(|(_, glyph)| glyph.iter().eq(pattern.iter()))
+            .map(|(letter, _)| *letter)
+            .ok_or(OcrError::UnknownGlyph { index, pattern })
+    }).collect::<Result<String, OcrError>>()?;
+    Ok(text.trim_end().to_string())
+}
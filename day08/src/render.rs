@@ -0,0 +1,128 @@
+// image export and terminal animation of the Screen.
+//
+// Both image formats are written by hand to keep the crate free of dependencies:
+// PBM is plain text, the PNG is 8 bit grayscale with uncompressed deflate blocks.
+// Lit pixels are black, every pixel becomes a scale x scale square.
+
+use crate::{Screen, ScreenOp};
+use std::io::{self, Write};
+use std::time::Duration;
+
+fn lit(screen: &Screen, xi: usize, yi: usize) -> bool {
+    screen.pixels[yi * screen.width + xi] != 0
+}
+
+// the rows of the scaled image, true is a lit pixel
+fn scaled_rows(screen: &Screen, scale: usize) -> impl Iterator<Item = Vec<bool>> + '_ {
+    (0..screen.height * scale).map(move |yi| {
+        (0..screen.width * scale).map(|xi| lit(screen, xi / scale, yi / scale)).collect()
+    })
+}
+
+// a scale of 0 would give an empty image, which neither format allows
+fn check_scale(scale: usize) -> io::Result<()> {
+    if scale == 0 {
+        Err(io::Error::new(io::ErrorKind::InvalidInput, "scale must be at least 1"))
+    } else {
+        Ok(())
+    }
+}
+
+pub fn write_pbm<W: Write>(screen: &Screen, scale: usize, out: &mut W) -> io::Result<()> {
+    check_scale(scale)?;
+    writeln!(out, "P1")?;
+    writeln!(out, "{} {}", screen.width * scale, screen.height * scale)?;
+    for row in scaled_rows(screen, scale) {
+        let row = row.iter().map(|pixel| if *pixel {"1"} else {"0"}).collect::<Vec<_>>();
+        writeln!(out, "{}", row.join(" "))?;
+    }
+    Ok(())
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffffffffu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {(crc >> 1) ^ 0xedb88320} else {crc >> 1};
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+fn write_chunk<W: Write>(out: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    let mut body = kind.to_vec();
+    body.extend_from_slice(data);
+    out.write_all(&body)?;
+    out.write_all(&crc32(&body).to_be_bytes())
+}
+
+pub fn write_png<W: Write>(screen: &Screen, scale: usize, out: &mut W) -> io::Result<()> {
+    check_scale(scale)?;
+    let (width, height) = (screen.width * scale, screen.height * scale);
+    out.write_all(b"\x89PNG\r\n\x1a\n")?;
+    let mut header = Vec::new();
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // bit depth 8, grayscale, deflate, default filtering, no interlacing
+    header.extend_from_slice(&[8, 0, 0, 0, 0]);
+    write_chunk(out, b"IHDR", &header)?;
+
+    // every row starts with filter type 0 (none)
+    let mut raw = Vec::with_capacity((width + 1) * height);
+    for row in scaled_rows(screen, scale) {
+        raw.push(0);
+        raw.extend(row.iter().map(|pixel| if *pixel {0x00} else {0xff}));
+    }
+    // zlib stream made of stored blocks, at most 65535 bytes each
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = raw.chunks(65535).peekable();
+    if blocks.peek().is_none() {
+        zlib.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        zlib.push(if blocks.peek().is_none() {1} else {0});
+        zlib.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        zlib.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+    write_chunk(out, b"IDAT", &zlib)?;
+    write_chunk(out, b"IEND", &[])
+}
+
+// one frame for the terminal, every pixel is two characters wide to keep it roughly square
+fn write_ansi_frame<W: Write>(screen: &Screen, out: &mut W) -> io::Result<()> {
+    for yi in 0..screen.height {
+        let row = (0..screen.width).map(|xi| if lit(screen, xi, yi) {"██"} else {"  "}).collect::<String>();
+        writeln!(out, "{}", row)?;
+    }
+    Ok(())
+}
+
+// plays the ops on an empty screen, drawing a frame after each of them
+pub fn animate<W: Write>(ops: &[ScreenOp], width: usize, height: usize, delay: Duration, out: &mut W) -> io::Result<()> {
+    let mut screen = Screen::new(width, height);
+    // clear the terminal and hide the cursor
+    write!(out, "\x1b[2J\x1b[?25l")?;
+    for (index, op) in ops.iter().enumerate() {
+        screen.perform_mut(*op);
+        // back to the top left, overwriting the previous frame; clear the rest of the caption line
+        writeln!(out, "\x1b[H{}/{} {:?}\x1b[K", index + 1, ops.len(), op)?;
+        write_ansi_frame(&screen, out)?;
+        out.flush()?;
+        std::thread::sleep(delay);
+    }
+    write!(out, "\x1b[?25h")?;
+    out.flush()
+}
@@ -0,0 +1,79 @@
+// encoder for the marker format.
+//
+// Greedy: at every position we look for the repeated unit that saves the most bytes,
+// counting the marker and one copy of the unit as the cost, and emit a marker if anything
+// is saved. In v2 the unit is encoded again, so repeats within repeats compress as well.
+//
+// Plain text may look like markers, so a literal '(' must never be followed by a literal digit:
+// such a digit is wrapped in (1x1). In v2 a unit must not end with '(', since the next copy
+// or the text after it would follow it directly.
+
+use crate::stream::Version;
+
+// longest repeated unit we look for
+const MAX_PERIOD: usize = 128;
+
+fn marker(letters: usize, repeats: usize) -> String {
+    format!("({}x{})", letters, repeats)
+}
+
+// the unit length and number of repeats saving the most bytes at start, if any
+fn best_repeat(text: &[u8], start: usize, version: Version) -> Option<(usize, usize)> {
+    let mut best = None;
+    let mut best_saving = 0;
+    for period in 1..=MAX_PERIOD.min((text.len() - start) / 2) {
+        let unit = &text[start..start + period];
+        if version == Version::V2 && unit.last() == Some(&b'(') {
+            continue;
+        }
+        let mut repeats = 1;
+        while text[start + repeats * period..].starts_with(unit) {
+            repeats += 1;
+        }
+        // estimated with the plain unit, its encoding in v2 is never longer than that by much
+        let saving = (period * repeats) as isize - (marker(period, repeats).len() + period) as isize;
+        if saving > best_saving {
+            best_saving = saving;
+            best = Some((period, repeats));
+        }
+    }
+    best
+}
+
+fn encode(text: &[u8], version: Version) -> String {
+    let mut result = String::new();
+    let mut pos = 0;
+    while pos < text.len() {
+        if let Some((period, repeats)) = best_repeat(text, pos, version) {
+            let unit = &text[pos..pos + period];
+            let data = match version {
+                Version::V1 => String::from_utf8(unit.to_vec()).unwrap(),
+                Version::V2 => encode(unit, version)
+            };
+            result.push_str(&marker(data.len(), repeats));
+            result.push_str(&data);
+            pos += period * repeats;
+            continue;
+        }
+        let byte = text[pos];
+        if byte.is_ascii_digit() && result.ends_with('(') {
+            result.push_str(&marker(1, 1));
+        }
+        result.push(byte as char);
+        pos += 1;
+    }
+    result
+}
+
+// text must be ascii, markers count bytes
+pub fn compress(text: &str, version: Version) -> Result<String, String> {
+    if !text.is_ascii() {
+        return Err("only ascii text can be compressed".to_string());
+    }
+    Ok(encode(text.as_bytes(), version))
+}
+
+// compressed size relative to the plain text, below 1 means it got smaller
+pub fn ratio(plain: usize, compressed: usize) -> f64 {
+    if plain == 0 {1.0} else {compressed as f64 / plain as f64}
+}
@@ -0,0 +1,150 @@
+// random access into decompressed output without expanding it.
+//
+// The input is parsed into a tree: runs of literal text and markers, each marker holding
+// the tree of its data. Every level keeps the offsets at which its parts start in the output,
+// so finding offset N is a binary search per level plus a modulo for every repeat on the way.
+//
+// In v2 this only works if every marker stays inside the data it is repeated in, which is the
+// case for puzzle inputs. Markers reaching out of their data (see stream.rs) are rejected.
+
+use crate::stream::Version;
+use crate::{Marker, MarkerAt};
+
+enum Node {
+    // text[start..end]
+    Literal(usize, usize),
+    Repeat(Marker, Sequence)
+}
+
+struct Sequence {
+    nodes: Vec<Node>,
+    // output offset at which every node starts
+    starts: Vec<usize>,
+    len: usize
+}
+
+pub struct Index {
+    text: Vec<u8>,
+    root: Sequence
+}
+
+impl Sequence {
+    fn push(&mut self, node: Node, len: usize) -> Result<(), String> {
+        self.starts.push(self.len);
+        self.nodes.push(node);
+        self.len = self.len.checked_add(len).ok_or("decompressed length exceeds usize::MAX")?;
+        Ok(())
+    }
+
+    // parses text[start..end]; nested is set for the data of a marker, which has to stand on its own
+    fn parse(text: &[u8], start: usize, end: usize, version: Version, nested: bool) -> Result<Sequence, String> {
+        let mut sequence = Sequence { nodes: Vec::new(), starts: Vec::new(), len: 0 };
+        let mut literal_start = start;
+        let mut pos = start;
+        while pos < end {
+            let marker = match Marker::at(text, pos, end) {
+                MarkerAt::Found(marker) => marker,
+                MarkerAt::Incomplete if nested => {
+                    return Err(format!("marker at offset {} is cut off by the end of the data it is repeated in", pos));
+                },
+                _ => {
+                    pos += 1;
+                    continue;
+                }
+            };
+            if literal_start < pos {
+                sequence.push(Node::Literal(literal_start, pos), pos - literal_start)?;
+            }
+            let data_end = marker.endidx.saturating_add(marker.letters);
+            if data_end > end && nested {
+                return Err(format!("marker at offset {} reaches beyond the data it is repeated in", pos));
+            }
+            // at the end of the input, the data is what is left
+            let data_end = data_end.min(end);
+            let body = match version {
+                Version::V1 => {
+                    let mut body = Sequence { nodes: Vec::new(), starts: Vec::new(), len: 0 };
+                    body.push(Node::Literal(marker.endidx, data_end), data_end - marker.endidx)?;
+                    body
+                },
+                Version::V2 => Sequence::parse(text, marker.endidx, data_end, version, true)?
+            };
+            let len = body.len.checked_mul(marker.repeats).ok_or("decompressed length exceeds usize::MAX")?;
+            if len > 0 {
+                sequence.push(Node::Repeat(marker, body), len)?;
+            }
+            pos = data_end;
+            literal_start = pos;
+        }
+        if literal_start < end {
+            sequence.push(Node::Literal(literal_start, end), end - literal_start)?;
+        }
+        Ok(sequence)
+    }
+
+    // the node containing offset, and the offset within it
+    fn find(&self, offset: usize) -> (&Node, usize) {
+        let index = self.starts.partition_point(|start| *start <= offset) - 1;
+        (&self.nodes[index], offset - self.starts[index])
+    }
+
+    fn byte_at(&self, text: &[u8], offset: usize) -> u8 {
+        match self.find(offset) {
+            (Node::Literal(start, _), offset) => text[start + offset],
+            (Node::Repeat(_, body), offset) => body.byte_at(text, offset % body.len)
+        }
+    }
+
+    // appends the output in start..end, which lies within this sequence
+    fn extract(&self, text: &[u8], start: usize, end: usize, out: &mut Vec<u8>) {
+        let mut offset = start;
+        while offset < end {
+            let index = self.starts.partition_point(|start| *start <= offset) - 1;
+            let node_start = self.starts[index];
+            let node_end = self.starts.get(index + 1).copied().unwrap_or(self.len).min(end);
+            match &self.nodes[index] {
+                Node::Literal(literal, _) => {
+                    out.extend_from_slice(&text[literal + offset - node_start..literal + node_end - node_start]);
+                },
+                Node::Repeat(_, body) => {
+                    // copy by copy, only the first and the last may be partial
+                    let mut inner = offset - node_start;
+                    while node_start + inner < node_end {
+                        let within = inner % body.len;
+                        let take = (body.len - within).min(node_end - node_start - inner);
+                        body.extract(text, within, within + take, out);
+                        inner += take;
+                    }
+                }
+            }
+            offset = node_end;
+        }
+    }
+}
+
+impl Index {
+    pub fn new(text: &str, version: Version) -> Result<Index, String> {
+        let text = text.as_bytes().to_vec();
+        let root = Sequence::parse(&text, 0, text.len(), version, false)?;
+        Ok(Index { text, root })
+    }
+
+    // length of the decompressed output
+    pub fn len(&self) -> usize {
+        self.root.len
+    }
+
+    pub fn byte_at(&self, offset: usize) -> Option<u8> {
+        if offset < self.root.len {Some(self.root.byte_at(&self.text, offset))} else {None}
+    }
+
+    // output bytes start..end, None if that is not within the output
+    pub fn bytes(&self, start: usize, end: usize) -> Option<Vec<u8>> {
+        if start > end || end > self.root.len {
+            return None;
+        }
+        let mut out = Vec::with_capacity(end - start);
+        self.root.extract(&self.text, start, end, &mut out);
+        Some(out)
+    }
+}
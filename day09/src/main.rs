@@ -4,8 +4,24 @@
 use regex::{Regex, Captures};
 use std::cmp::max;
 
-fn decompress(text: &str) -> String {
+mod compress;
+mod index;
+mod stream;
+mod validate;
+
+use validate::{FormatError, Problem};
+
+// the first problem that stops decompression, the regex below would panic on it
+fn first_stopping(text: &str, version: stream::Version) -> Result<(), FormatError> {
+    match validate::validate(text, version).into_iter().find(|err| err.problem.stops_decompression()) {
+        Some(err) => Err(err),
+        None => Ok(())
+    }
+}
+
+fn decompress(text: &str) -> Result<String, FormatError> {
     // we assume that text is ascii only
+    first_stopping(text, stream::Version::V1)?;
 
     let re = Regex::new(r"\((\d+)x(\d+)\)").unwrap();
     let mut i = 0;
@@ -33,7 +49,7 @@ fn decompress(text: &str) -> String {
     if i < text.len() {
         result.push_str(&text[i..text.len()]);
     }
-    result
+    Ok(result)
 }
 
 #[derive(Debug, Hash, PartialEq, Eq)]
@@ -50,67 +66,170 @@ impl Marker {
     fn new(startidx: usize, endidx: usize, letters: usize, repeats: usize) -> Marker {
         Marker { startidx, endidx, letters, repeats, virtualrepeats: repeats, virtualletters: letters}
     }
+
+    // the marker starting at text[start], looking no further than end
+    fn at(text: &[u8], start: usize, end: usize) -> MarkerAt {
+        if text[start] != b'(' {
+            return MarkerAt::NotMarker;
+        }
+        let mut pos = start + 1;
+        let mut nums = [0usize; 2];
+        let mut too_large = false;
+        for (index, terminator) in [b'x', b')'].into_iter().enumerate() {
+            let digits = pos;
+            while pos < end && text[pos].is_ascii_digit() {
+                match nums[index].checked_mul(10).and_then(|num| num.checked_add((text[pos] - b'0') as usize)) {
+                    Some(value) => nums[index] = value,
+                    None => too_large = true
+                }
+                pos += 1;
+            }
+            if pos == end {
+                return MarkerAt::Incomplete;
+            }
+            if text[pos] != terminator || pos == digits {
+                return MarkerAt::NotMarker;
+            }
+            pos += 1;
+        }
+        if too_large {
+            return MarkerAt::TooLarge;
+        }
+        MarkerAt::Found(Marker::new(start, pos, nums[0], nums[1]))
+    }
 }
 
-fn decompress_v2(text: &str) -> usize {
+enum MarkerAt {
+    Found(Marker),
+    NotMarker,
+    // a marker with a number that does not fit in usize
+    TooLarge,
+    // text[..end] ends within something that may still become a marker
+    Incomplete
+}
+
+fn decompress_v2(text: &str) -> Result<usize, FormatError> {
     // we assume that text is ascii only
     // only gets size of decompressed content
-    //
-    // we make some simplifying assumptions, mainly that
-    // markers do no partial repeats if other markers' scopes.
-    // i.e. something like (6x2)(3x3)ABC
-    // would be not be correctly dealt with,
-    // as we assume that the whole scope of (3x3) gets copied
-    use std::collections::HashMap;
+    first_stopping(text, stream::Version::V2)?;
+    match stream::Stream::new(text.as_bytes(), stream::V2_STEP_LIMIT).measure(false, &mut 0) {
+        stream::Length::Done(length) => Ok(length),
+        _ => Err(FormatError {
+            offset: 0,
+            problem: Problem::TooLong,
+            message: "v2 decompression does not end in reasonable time or exceeds usize::MAX bytes".to_string()
+        })
+    }
+}
 
-    let re = Regex::new(r"\((\d+)x(\d+)\)").unwrap();
-    let mut i = text.len();
-    let mut result = 0;
-    let match_vec = re.captures_iter(text).collect::<Vec<_>>();
-    let mut markers = Vec::new();
-    for (i, mtch) in match_vec.iter().enumerate() {
-        let mtch1 = mtch.get(1).unwrap();
-        let mtch2 = mtch.get(2).unwrap();
-        let startidx = mtch1.start() - 1;
-        let endidx = mtch2.end() + 1;
-        let letters = mtch1.as_str().parse::<usize>().unwrap();
-        let repeats = mtch2.as_str().parse::<usize>().unwrap();
-        markers.push(Marker::new(startidx, endidx, letters, repeats));
-        for j in (0..i).rev() {
-            if markers[j].endidx + markers[j].letters <= startidx {
-                continue;
-            }
-            // another assumption: if marker has another marker in scope,
-            // there are no terminals before the inner marker
-            markers[j].virtualletters = 0;
-            markers[i].virtualrepeats *= markers[j].repeats;
+fn part1(lines: &Vec<&str>) -> Option<usize> {
+    assert!(lines.len() == 1);
+    match decompress(lines[0]) {
+        Ok(decompressed) => Some(decompressed.chars().filter(|c| !c.is_ascii_whitespace()).count()),
+        Err(err) => {
+            println!("Invalid input at {}", err);
+            None
         }
     }
-    for (j, marker) in markers.iter().enumerate() {
-        result += marker.virtualrepeats * marker.virtualletters;
+}
+
+fn part2(lines: &Vec<&str>) -> Option<usize> {
+    assert!(lines.len() == 1);
+    match decompress_v2(lines[0]) {
+        Ok(length) => Some(length),
+        Err(err) => {
+            println!("Invalid input at {}", err);
+            None
+        }
     }
-    let mut i = 0;
-    // find any unbound letters
-    i = markers[0].endidx + markers[0].letters;
-    result += markers[0].startidx;
-    for (j, marker) in markers.iter().enumerate() {
-        if marker.startidx > i {
-            result += marker.startidx - i;
+}
+
+fn version_arg(args: &[String]) -> stream::Version {
+    match args.get(2).map(|a| a.as_str()) {
+        Some("v1") => stream::Version::V1,
+        Some("v2") => stream::Version::V2,
+        _ => {
+            println!("Expected the format version, v1 or v2");
+            std::process::exit(1);
         }
-        i = max(i, marker.endidx + marker.letters);
     }
-    result
 }
 
-fn part1(lines: &Vec<&str>) -> Option<usize> {
-    assert!(lines.len() == 1);
-    let decompressed = decompress(lines[0]);
-    Some(decompressed.chars().filter(|c| !c.is_ascii_whitespace()).count())
+fn infile_arg(args: &[String]) -> &str {
+    args.get(3).unwrap_or_else(|| {
+        println!("Missing argument, run without arguments for usage");
+        std::process::exit(1);
+    })
 }
 
-fn part2(lines: &Vec<&str>) -> Option<usize> {
-    assert!(lines.len() == 1);
-    Some(decompress_v2(lines[0]))
+// writes the decompressed input to stdout, without holding it in memory
+fn decompress_main(args: &[String]) {
+    use std::io::{BufReader, BufWriter};
+    let version = version_arg(args);
+    let infile = infile_arg(args);
+    let file = std::fs::File::open(infile).expect("Could not read in file");
+    let mut decompressor = stream::Decompressor::new(BufReader::new(file), version);
+    let mut out = BufWriter::new(std::io::stdout().lock());
+    match std::io::copy(&mut decompressor, &mut out) {
+        Ok(_) => {},
+        // e.g. piped into head
+        Err(err) if err.kind() == std::io::ErrorKind::BrokenPipe => {},
+        Err(err) => {
+            eprintln!("Decompression failed: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+// writes the compressed input to stdout and how well it did to stderr
+fn compress_main(args: &[String]) {
+    let version = version_arg(args);
+    let contents = std::fs::read_to_string(infile_arg(args)).expect("Could not read in file");
+    let compressed = compress::compress(&contents, version).unwrap_or_else(|err| {
+        println!("{}", err);
+        std::process::exit(1);
+    });
+    print!("{}", compressed);
+    eprintln!("{} -> {} bytes, ratio {:.3}", contents.len(), compressed.len(), compress::ratio(contents.len(), compressed.len()));
+}
+
+// prints the output byte at an offset, or the bytes in a range, without decompressing
+fn index_main(args: &[String]) {
+    let version = version_arg(args);
+    let contents = std::fs::read_to_string(infile_arg(args)).expect("Could not read in file");
+    let index = index::Index::new(contents.trim_end(), version).unwrap_or_else(|err| {
+        println!("{}", err);
+        std::process::exit(1);
+    });
+    let offset = |position: usize| -> Option<usize> {
+        args.get(position).map(|a| a.parse().expect("Offsets must be numbers"))
+    };
+    let start = offset(4).unwrap_or_else(|| {
+        println!("Missing offset, run without arguments for usage");
+        std::process::exit(1);
+    });
+    let end = offset(5).unwrap_or(start + 1);
+    match index.bytes(start, end) {
+        Some(bytes) => println!("{}", String::from_utf8_lossy(&bytes)),
+        None => {
+            println!("{}..{} is not within the output of {} bytes", start, end, index.len());
+            std::process::exit(1);
+        }
+    }
+}
+
+// lists every problem in the markers of the input, exits with 1 if there is any
+fn validate_main(args: &[String]) {
+    let version = version_arg(args);
+    let contents = std::fs::read_to_string(infile_arg(args)).expect("Could not read in file");
+    let errors = validate::validate(contents.trim_end(), version);
+    for err in &errors {
+        println!("{}", err);
+    }
+    if !errors.is_empty() {
+        std::process::exit(1);
+    }
+    println!("No problems found");
 }
 
 fn main() {
@@ -118,8 +237,28 @@ fn main() {
     use std::env;
     use std::time::Instant;
     let args: Vec<String> =  env::args().collect();
+    if args.len() > 1 && args[1] == "--decompress" {
+        decompress_main(&args);
+        return;
+    }
+    if args.len() > 1 && args[1] == "--compress" {
+        compress_main(&args);
+        return;
+    }
+    if args.len() > 1 && args[1] == "--index" {
+        index_main(&args);
+        return;
+    }
+    if args.len() > 1 && args[1] == "--validate" {
+        validate_main(&args);
+        return;
+    }
     let infile = args.get(1).unwrap_or_else(|| {
         println!("Usage: {} <puzzle input>", args[0]);
+        println!("       {} --decompress <v1|v2> <puzzle input>", args[0]);
+        println!("       {} --compress <v1|v2> <text file>", args[0]);
+        println!("       {} --index <v1|v2> <puzzle input> <offset> [end offset]", args[0]);
+        println!("       {} --validate <v1|v2> <puzzle input>", args[0]);
         std::process::exit(1);
     });
 
@@ -147,20 +286,325 @@ mod tests {
 
     #[test]
     fn test_decompress() {
-        assert_eq!("ABBBBBC", decompress("A(1x5)BC").as_str());
-        assert_eq!("ADVENT", decompress("ADVENT").as_str());
-        assert_eq!("(1x3)A", decompress("(6x1)(1x3)A").as_str());
-        assert_eq!("X(3x3)ABC(3x3)ABCY", decompress("X(8x2)(3x3)ABCY").as_str());
+        assert_eq!("ABBBBBC", decompress("A(1x5)BC").unwrap());
+        assert_eq!("ADVENT", decompress("ADVENT").unwrap());
+        assert_eq!("(1x3)A", decompress("(6x1)(1x3)A").unwrap());
+        assert_eq!("X(3x3)ABC(3x3)ABCY", decompress("X(8x2)(3x3)ABCY").unwrap());
     }
 
     #[test]
     fn test_decompress_v2() {
-        assert_eq!(9, decompress_v2("(3x3)XYZ"));
-        assert_eq!(241920, decompress_v2("(27x12)(20x12)(13x14)(7x10)(1x12)A"));
+        assert_eq!(9, decompress_v2("(3x3)XYZ").unwrap());
+        assert_eq!(241920, decompress_v2("(27x12)(20x12)(13x14)(7x10)(1x12)A").unwrap());
     }
 
     #[test]
     fn test_decompress_v2_hard() {
-        assert_eq!(445, decompress_v2("(25x3)(3x3)ABC(2x3)XY(5x2)PQRSTX(18x9)(3x2)TWO(5x7)SEVEN"));
+        assert_eq!(445, decompress_v2("(25x3)(3x3)ABC(2x3)XY(5x2)PQRSTX(18x9)(3x2)TWO(5x7)SEVEN").unwrap());
+    }
+
+    // v2 by the letter: decompressed data is put back in front of the input.
+    // None once the output exceeds limit bytes or after limit steps
+    fn expand_v2_naive(text: &str, limit: usize) -> Option<String> {
+        use std::collections::VecDeque;
+        let re = Regex::new(r"^\((\d+)x(\d+)\)").unwrap();
+        let mut input = text.bytes().collect::<VecDeque<_>>();
+        let mut output = String::new();
+        for _ in 0..limit {
+            let front = String::from_utf8(input.iter().take(50).copied().collect()).unwrap();
+            match re.captures(&front).and_then(|c| Some((c[0].len(), c[1].parse::<usize>().ok()?, c[2].parse::<usize>().ok()?))) {
+                Some((length, letters, repeats)) => {
+                    input.drain(..length);
+                    let data = input.drain(..letters.min(input.len())).collect::<Vec<_>>();
+                    for _ in 0..repeats {
+                        for byte in data.iter().rev() {
+                            input.push_front(*byte);
+                        }
+                    }
+                },
+                None => match input.pop_front() {
+                    Some(byte) => output.push(byte as char),
+                    None => return Some(output)
+                }
+            }
+            if output.len() > limit {
+                return None;
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn test_decompress_v2_partial_scopes() {
+        // the first copy of (3x3) repeats "A(3", taken from the second copy
+        assert_eq!("A(3A(3AABCABCABC", expand_v2_naive("(6x2)(3x3)ABC", 1000).unwrap());
+        assert_eq!(16, decompress_v2("(6x2)(3x3)ABC").unwrap());
+        // a marker spanning two copies of the data
+        assert_eq!(expand_v2_naive("(3x2)(1xA3)B", 1000).unwrap().len(), decompress_v2("(3x2)(1xA3)B").unwrap());
+        // markers running past the end of the input are an error, the stream takes what is left
+        assert_eq!(Problem::PastEnd, decompress_v2("(9x3)A").unwrap_err().problem);
+        assert_eq!("AAA", read_all("(9x3)A", stream::Version::V2, 4096));
+    }
+
+    #[test]
+    fn test_decompress_v2_endless() {
+        // reproduces itself forever
+        let mut stream = stream::Stream::new("(5x2)(5x2)".as_bytes(), 10_000);
+        assert!(matches!(stream.measure(false, &mut 0), stream::Length::TooLong));
+        // 2^64 bytes, too long to count even though every marker is closed
+        let nested = |levels: usize| (0..levels).fold("A".to_string(), |text, _| format!("({}x2){}", text.len(), text));
+        assert!(matches!(stream::Stream::new(nested(64).as_bytes(), 10_000).measure(false, &mut 0), stream::Length::TooLong));
+        assert_eq!(1 << 40, decompress_v2(&nested(40)).unwrap());
+    }
+
+    fn read_all(text: &str, version: stream::Version, chunk: usize) -> String {
+        use std::io::Read;
+        let mut decompressor = stream::Decompressor::new(text.as_bytes(), version);
+        let mut result = Vec::new();
+        let mut buffer = vec![0u8; chunk];
+        loop {
+            match decompressor.read(&mut buffer).unwrap() {
+                0 => break,
+                count => result.extend_from_slice(&buffer[..count])
+            }
+        }
+        String::from_utf8(result).unwrap()
+    }
+
+    #[test]
+    fn test_stream_v1() {
+        for text in ["A(1x5)BC", "ADVENT", "(6x1)(1x3)A", "X(8x2)(3x3)ABCY", "(3x3)XYZ(2x2)AB", ""] {
+            for chunk in [1, 3, 4096] {
+                assert_eq!(decompress(text).unwrap(), read_all(text, stream::Version::V1, chunk), "for {}", text);
+            }
+        }
+    }
+
+    #[test]
+    fn test_stream_v2() {
+        assert_eq!("XABCABCABCABCABCABCY", read_all("X(8x2)(3x3)ABCY", stream::Version::V2, 5));
+        assert_eq!("A(3A(3AABCABCABC", read_all("(6x2)(3x3)ABC", stream::Version::V2, 2));
+        let text = "(25x3)(3x3)ABC(2x3)XY(5x2)PQRSTX(18x9)(3x2)TWO(5x7)SEVEN";
+        assert_eq!(445, read_all(text, stream::Version::V2, 7).len());
+    }
+
+    #[test]
+    fn test_stream_large_output() {
+        use std::io::Read;
+        // a million bytes, counted without holding them
+        let nested = (0..20).fold("A".to_string(), |text, _| format!("({}x2){}", text.len(), text));
+        let mut decompressor = stream::Decompressor::new(nested.as_bytes(), stream::Version::V2);
+        assert_eq!(1 << 20, std::io::copy(&mut decompressor, &mut std::io::sink()).unwrap());
+        // the first bytes of an endless expansion
+        let mut endless = stream::Decompressor::new("(6x2)A(6x2)".as_bytes(), stream::Version::V2).take(12);
+        let mut start = String::new();
+        endless.read_to_string(&mut start).unwrap();
+        assert_eq!("A".repeat(12), start);
+    }
+
+    #[test]
+    fn test_stream_without_output() {
+        use std::io::Read;
+        // reproduces itself forever without any output
+        let mut decompressor = stream::Decompressor::with_step_limit("(5x2)(5x2)".as_bytes(), stream::Version::V2, 10_000);
+        let mut buffer = [0u8; 8];
+        assert!(decompressor.read(&mut buffer).is_err());
+        // output first, then the endless part
+        let mut decompressor = stream::Decompressor::with_step_limit("AB(5x2)(5x2)".as_bytes(), stream::Version::V2, 10_000);
+        assert_eq!(2, decompressor.read(&mut buffer).unwrap());
+        assert!(decompressor.read(&mut buffer).is_err());
+        // many markers, but output between them
+        let text = "(1x1)A".repeat(100);
+        let mut decompressor = stream::Decompressor::with_step_limit(text.as_bytes(), stream::Version::V2, 2);
+        let mut result = String::new();
+        decompressor.read_to_string(&mut result).unwrap();
+        assert_eq!("A".repeat(100), result);
+    }
+
+    #[test]
+    fn test_stream_read_errors() {
+        use std::io::Read;
+        struct Failing;
+        impl Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("disk on fire"))
+            }
+        }
+        let mut decompressor = stream::Decompressor::new("(1x3)A".as_bytes().chain(Failing), stream::Version::V1);
+        let mut buffer = [0u8; 8];
+        assert_eq!(3, decompressor.read(&mut buffer).unwrap());
+        assert_eq!("disk on fire", decompressor.read(&mut buffer).unwrap_err().to_string());
+    }
+
+    fn assert_roundtrip(text: &str) {
+        let compressed = compress::compress(text, stream::Version::V1).unwrap();
+        assert_eq!(text, decompress(&compressed).unwrap(), "v1 {:?} -> {:?}", text, compressed);
+        let compressed = compress::compress(text, stream::Version::V2).unwrap();
+        assert_eq!(text, read_all(&compressed, stream::Version::V2, 4096), "v2 {:?} -> {:?}", text, compressed);
+        assert_eq!(text.len(), decompress_v2(&compressed).unwrap());
+    }
+
+    #[test]
+    fn test_compress() {
+        assert_eq!("ADVENT", compress::compress("ADVENT", stream::Version::V1).unwrap());
+        assert_eq!("(3x10)ABC", compress::compress(&"ABC".repeat(10), stream::Version::V1).unwrap());
+        // v2 compresses the repeated unit again
+        let text = ("XY".repeat(8) + "Z").repeat(20);
+        assert_eq!("(8x20)(2x8)XYZ", compress::compress(&text, stream::Version::V2).unwrap());
+        assert_eq!("(17x20)XYXYXYXYXYXYXYXYZ", compress::compress(&text, stream::Version::V1).unwrap());
+        assert!(compress::compress("Ünïcödé", stream::Version::V1).is_err());
+        for text in ["", "A", "ADVENT", "A(1x5)BC", "(3x3)XYZ", "((1x2)", "(((((1", "(1(1(1(1(1(1(", "12(x)(3x", &"(2x2)".repeat(9)] {
+            assert_roundtrip(text);
+        }
+    }
+
+    #[test]
+    fn test_compress_ratio() {
+        // the puzzle examples decompress to more than they compress to, but not as well as we do
+        for example in ["X(8x2)(3x3)ABCY", "(27x12)(20x12)(13x14)(7x10)(1x12)A"] {
+            let plain = read_all(example, stream::Version::V2, 4096);
+            let ours = compress::compress(&plain, stream::Version::V2).unwrap();
+            assert!(ours.len() <= example.len(), "{} vs {}", ours, example);
+            assert!(compress::ratio(plain.len(), ours.len()) < 1.0);
+        }
+        assert_eq!(1.0, compress::ratio(0, 0));
+    }
+
+    #[test]
+    fn test_compress_roundtrip_random() {
+        let pieces = ["A", "B", "(", ")", "x", "1", "2", "3", "(1x2)", " "];
+        let mut seed = 0x1234567u32;
+        let mut next = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed as usize
+        };
+        for _ in 0..300 {
+            // random pieces, some of them repeated to give the encoder something to find
+            let mut text = String::new();
+            for _ in 0..1 + next() % 8 {
+                let unit = (0..1 + next() % 6).map(|_| pieces[next() % pieces.len()]).collect::<String>();
+                text.push_str(&unit.repeat(1 + next() % 12));
+            }
+            assert_roundtrip(&text);
+        }
+    }
+
+    fn assert_index_matches(text: &str, version: stream::Version) {
+        let expanded = read_all(text, version, 4096).into_bytes();
+        let index = index::Index::new(text, version).unwrap();
+        assert_eq!(expanded.len(), index.len(), "for {}", text);
+        for (offset, byte) in expanded.iter().enumerate() {
+            assert_eq!(Some(*byte), index.byte_at(offset), "at {} for {}", offset, text);
+        }
+        assert_eq!(None, index.byte_at(expanded.len()));
+        for start in 0..=expanded.len().min(40) {
+            for end in start..=expanded.len().min(60) {
+                assert_eq!(Some(expanded[start..end].to_vec()), index.bytes(start, end), "{}..{} for {}", start, end, text);
+            }
+        }
+        assert_eq!(None, index.bytes(0, expanded.len() + 1));
+    }
+
+    #[test]
+    fn test_index() {
+        for text in ["ADVENT", "A(1x5)BC", "(3x3)XYZ", "A(2x2)BCD(2x2)EFG", "(6x1)(1x3)A", "X(8x2)(3x3)ABCY", "(9x3)A", ""] {
+            assert_index_matches(text, stream::Version::V1);
+            assert_index_matches(text, stream::Version::V2);
+        }
+        assert_index_matches("(25x3)(3x3)ABC(2x3)XY(5x2)PQRSTX(18x9)(3x2)TWO(5x7)SEVEN", stream::Version::V2);
+        // compressed text is always well nested
+        let text = compress::compress(&("XY".repeat(8) + "Z(1x1)").repeat(20), stream::Version::V2).unwrap();
+        assert_index_matches(&text, stream::Version::V2);
+    }
+
+    #[test]
+    fn test_index_huge_output() {
+        // 2^50 bytes
+        let text = (0..50).fold("AB".to_string(), |text, _| format!("({}x2){}", text.len(), text));
+        let index = index::Index::new(&text, stream::Version::V2).unwrap();
+        assert_eq!(1 << 51, index.len());
+        assert_eq!(Some(b'B'), index.byte_at((1 << 51) - 1));
+        assert_eq!(Some(b"BABA".to_vec()), index.bytes((1 << 50) - 1, (1 << 50) + 3));
+        let too_long = format!("({}x100000){}", text.len(), text);
+        assert_eq!("decompressed length exceeds usize::MAX", index::Index::new(&too_long, stream::Version::V2).err().unwrap());
+    }
+
+    #[test]
+    fn test_index_rejects_open_markers() {
+        let err = index::Index::new("(6x2)(3x3)ABC", stream::Version::V2).err().unwrap();
+        assert_eq!("marker at offset 5 reaches beyond the data it is repeated in", err);
+        let err = index::Index::new("(3x2)AB(1x2)", stream::Version::V2).err().unwrap();
+        assert_eq!("marker at offset 7 is cut off by the end of the data it is repeated in", err);
+        // no nesting in v1
+        assert!(index::Index::new("(6x2)(3x3)ABC", stream::Version::V1).is_ok());
+    }
+
+    #[test]
+    fn test_decompress_v2_matches_naive_expansion() {
+        let tokens = ["A", "B", "(", ")", "x", "1", "2", "(1x2)", "(2x2)", "(3x3)", "(5x2)", "(6x2)", "(8x3)", "(4x0)", "(0x3)", "(2x"];
+        let mut seed = 0x9e3779b9u32;
+        let mut next = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed as usize
+        };
+        let mut compared = 0;
+        for _ in 0..2000 {
+            let len = 1 + next() % 12;
+            let text = (0..len).map(|_| tokens[next() % tokens.len()]).collect::<String>();
+            if let Some(expanded) = expand_v2_naive(&text, 2000) {
+                match stream::Stream::new(text.as_bytes(), stream::V2_STEP_LIMIT).measure(false, &mut 0) {
+                    stream::Length::Done(length) => assert_eq!(expanded.len(), length, "for {}", text),
+                    _ => panic!("no length for {}", text)
+                }
+                assert_eq!(expanded, read_all(&text, stream::Version::V2, 3), "for {}", text);
+                compared += 1;
+            }
+        }
+        assert!(compared > 1500);
+    }
+
+    #[test]
+    fn test_validate() {
+        use stream::Version::{V1, V2};
+        let problems = |text: &str, version| {
+            validate::validate(text, version).into_iter().map(|err| (err.offset, err.problem)).collect::<Vec<_>>()
+        };
+        for text in ["ADVENT", "A(1x5)BC", "X(8x2)(3x3)ABCY", "(6x2)(3x3)ABC", "(3x2)(1xA3)B", "(x)(A", "AB(", ""] {
+            assert!(validate::validate(text, V1).is_empty(), "for {}", text);
+            assert!(validate::validate(text, V2).is_empty(), "for {}", text);
+        }
+        assert_eq!(vec![(2, Problem::Malformed), (8, Problem::Malformed)], problems("AB(3x)CD(12x4", V1));
+        assert_eq!(vec![(0, Problem::Malformed)], problems("(1)", V2));
+        assert_eq!(vec![(1, Problem::PastEnd)], problems("A(9x3)BC", V1));
+        assert_eq!(vec![(7, Problem::Whitespace)], problems("A(3x3)B CD", V1));
+        let too_large = format!("({}x2)AB", usize::MAX as u128 + 1);
+        assert_eq!(vec![(0, Problem::TooLarge)], problems(&too_large, V1));
+        // v1 does not look into repeated data, v2 does
+        assert!(problems("(6x2)(3y3)A", V1).is_empty());
+        assert_eq!(vec![(5, Problem::Malformed)], problems("(6x2)(3y3)A", V2));
+        let err = &validate::validate("A(9x3)BC", V1)[0];
+        assert_eq!("offset 1: marker needs 9 bytes of data, only 2 left", err.to_string());
+    }
+
+    #[test]
+    fn test_decompress_errors() {
+        let err = decompress("AB(5x2)C").unwrap_err();
+        assert_eq!((2, Problem::PastEnd), (err.offset, err.problem));
+        let too_large = format!("A({}x2)B", usize::MAX as u128 + 1);
+        assert_eq!(Problem::TooLarge, decompress(&too_large).unwrap_err().problem);
+        assert_eq!(Problem::TooLarge, decompress_v2(&too_large).unwrap_err().problem);
+        let nested = (0..64).fold("A".to_string(), |text, _| format!("({}x2){}", text.len(), text));
+        assert_eq!(Problem::TooLong, decompress_v2(&nested).unwrap_err().problem);
+        // malformed markers and whitespace are only reported, the text is decompressed as it is
+        assert_eq!("(3x)A A A ", decompress("(3x)(2x3)A ").unwrap());
+        assert_eq!(5, decompress_v2("(3y3)").unwrap());
+        // markers count bytes, é takes two
+        let err = decompress("(1x2)éB").unwrap_err();
+        assert_eq!((0, Problem::SplitsCharacter), (err.offset, err.problem));
+        assert_eq!("ééB", decompress("(2x2)éB").unwrap());
     }
 }
@@ -0,0 +1,286 @@
+// decompression as a stream of frames, shared by the v2 length computation and the
+// streaming decompressor.
+//
+// v2 as the format specifies: repeated data is decompressed again, as if it was put back
+// in front of the rest of the input. A marker in repeated data may therefore reach beyond it,
+// as in (6x2)(3x3)ABC, where the first copy of (3x3) takes "A(3" from the second one.
+//
+// We keep the repeated data as a stack of frames in front of the input, each frame some data
+// repeated a number of times. A frame whose data is closed, i.e. decompresses on its own
+// without reading past its end, yields the same length every time, so it is measured once
+// (recursively) and multiplied. Only markers reaching out of their data have to be expanded
+// step by step, which can blow up or never end, so those steps are limited.
+//
+// v1 works the same way, except that its frames are literal: sent out as they are.
+
+use std::collections::VecDeque;
+use std::io::{self, Read};
+
+pub const V2_STEP_LIMIT: usize = 100_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    V1,
+    V2
+}
+
+struct Frame {
+    data: Vec<u8>,
+    pos: usize,
+    // full repetitions after the current one
+    repeats: usize,
+    // we already know that data is not closed
+    open: bool,
+    // v1 data, markers in it are not decompressed
+    literal: bool
+}
+
+// the bytes still to be decompressed: the top frame comes first, the source last
+pub struct Stream<R: Read> {
+    frames: Vec<Frame>,
+    source: R,
+    // bytes read from the source but not consumed yet
+    lookahead: VecDeque<u8>,
+    // the source failed, which ends the stream
+    error: Option<io::Error>,
+    step_limit: usize
+}
+
+enum Peeked {
+    // (lettersxrepeats), length of the marker text
+    Marker(usize, usize, usize),
+    Literal,
+    // the stream ended before we could tell
+    Incomplete
+}
+
+pub enum Length {
+    Done(usize),
+    // decompressing the data on its own reads past its end
+    Open,
+    // more than step_limit steps, or more than usize::MAX bytes
+    TooLong
+}
+
+impl<R: Read> Stream<R> {
+    pub fn new(source: R, step_limit: usize) -> Stream<R> {
+        Stream { frames: Vec::new(), source, lookahead: VecDeque::new(), error: None, step_limit }
+    }
+
+    // drops finished frames and starts the next repetition, so the top frame has data left
+    fn normalise(&mut self) {
+        while let Some(top) = self.frames.last_mut() {
+            if top.pos < top.data.len() {
+                break;
+            } else if top.repeats > 0 && !top.data.is_empty() {
+                top.repeats -= 1;
+                top.pos = 0;
+            } else {
+                self.frames.pop();
+            }
+        }
+    }
+
+    // reads from the source until the lookahead holds more than offset bytes, or the source ends
+    fn fill(&mut self, offset: usize) -> bool {
+        let mut buffer = [0u8; 4096];
+        while self.lookahead.len() <= offset && self.error.is_none() {
+            match self.source.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(count) => self.lookahead.extend(&buffer[..count]),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {},
+                Err(err) => self.error = Some(err)
+            }
+        }
+        offset < self.lookahead.len()
+    }
+
+    fn peek(&mut self, offset: usize) -> Option<u8> {
+        let mut offset = offset;
+        for frame in self.frames.iter().rev() {
+            let len = frame.data.len();
+            let available = (len - frame.pos).saturating_add(frame.repeats.saturating_mul(len));
+            if offset < available {
+                return Some(frame.data[(frame.pos + offset) % len]);
+            }
+            offset -= available;
+        }
+        if self.fill(offset) {Some(self.lookahead[offset])} else {None}
+    }
+
+    // removes up to count bytes from the front and appends them to result
+    fn take_into(&mut self, count: usize, result: &mut Vec<u8>) {
+        let mut count = count;
+        while count > 0 {
+            self.normalise();
+            match self.frames.last_mut() {
+                Some(top) => {
+                    let end = top.data.len().min(top.pos + count);
+                    result.extend_from_slice(&top.data[top.pos..end]);
+                    count -= end - top.pos;
+                    top.pos = end;
+                },
+                None => {
+                    if !self.fill(0) {
+                        break;
+                    }
+                    let available = count.min(self.lookahead.len());
+                    result.extend(self.lookahead.drain(..available));
+                    count -= available;
+                }
+            }
+        }
+    }
+
+    fn take(&mut self, count: usize) -> Vec<u8> {
+        let mut result = Vec::new();
+        self.take_into(count, &mut result);
+        result
+    }
+
+    fn peek_marker(&mut self) -> Peeked {
+        if self.peek(0) != Some(b'(') {
+            return Peeked::Literal;
+        }
+        let mut offset = 1;
+        let mut nums = [0usize; 2];
+        for (index, terminator) in [b'x', b')'].into_iter().enumerate() {
+            let start = offset;
+            loop {
+                match self.peek(offset) {
+                    Some(digit @ b'0'..=b'9') => {
+                        match nums[index].checked_mul(10).and_then(|num| num.checked_add((digit - b'0') as usize)) {
+                            Some(value) => nums[index] = value,
+                            None => return Peeked::Literal
+                        }
+                    },
+                    Some(byte) if byte == terminator && offset > start => break,
+                    Some(_) => return Peeked::Literal,
+                    None => return Peeked::Incomplete
+                }
+                offset += 1;
+            }
+            offset += 1;
+        }
+        Peeked::Marker(nums[0], nums[1], offset)
+    }
+
+    // consumes the marker at the front and puts its data on the stack.
+    // Returns false if the stream ended before all of its data was there
+    fn expand_marker(&mut self, letters: usize, repeats: usize, length: usize, literal: bool) -> bool {
+        self.take(length);
+        let data = self.take(letters);
+        let complete = data.len() == letters;
+        // frames the data was taken from may be finished, they must not pile up below the new one
+        self.normalise();
+        if repeats > 0 && !data.is_empty() {
+            self.frames.push(Frame { data, pos: 0, repeats: repeats - 1, open: false, literal });
+        }
+        complete
+    }
+
+    // decompressed length of the stream. If closed is set, the stream is the data of a marker
+    // that has to be decompressed on its own, and reading past its end gives Length::Open
+    pub fn measure(&mut self, closed: bool, steps: &mut usize) -> Length {
+        let mut total: usize = 0;
+        loop {
+            self.normalise();
+            if let Some(top) = self.frames.last_mut() {
+                if top.pos == 0 && !top.open {
+                    match Stream::new(top.data.as_slice(), self.step_limit).measure(true, steps) {
+                        Length::Done(length) => {
+                            let Some(length) = length.checked_mul(top.repeats + 1) else { return Length::TooLong };
+                            let Some(sum) = total.checked_add(length) else { return Length::TooLong };
+                            total = sum;
+                            self.frames.pop();
+                            continue;
+                        },
+                        Length::Open => top.open = true,
+                        Length::TooLong => return Length::TooLong
+                    }
+                }
+            }
+            *steps += 1;
+            if *steps > self.step_limit {
+                return Length::TooLong;
+            }
+            match self.peek_marker() {
+                Peeked::Incomplete if closed => return Length::Open,
+                Peeked::Marker(letters, repeats, length) => {
+                    if !self.expand_marker(letters, repeats, length, false) && closed {
+                        return Length::Open;
+                    }
+                },
+                _ => {
+                    if self.take(1).is_empty() {
+                        return Length::Done(total);
+                    }
+                    total += 1;
+                }
+            }
+        }
+    }
+}
+
+// wraps a reader of compressed data into a reader of the decompressed data.
+// Memory use only depends on the marker spans, not on the length of the output
+pub struct Decompressor<R: Read> {
+    stream: Stream<R>,
+    version: Version
+}
+
+impl<R: Read> Decompressor<R> {
+    pub fn new(source: R, version: Version) -> Decompressor<R> {
+        Decompressor::with_step_limit(source, version, V2_STEP_LIMIT)
+    }
+
+    // step_limit bounds the markers expanded in a row without any output in between,
+    // as in (5x2)(5x2), which reproduces itself forever
+    pub fn with_step_limit(source: R, version: Version, step_limit: usize) -> Decompressor<R> {
+        Decompressor { stream: Stream::new(source, step_limit), version }
+    }
+}
+
+impl<R: Read> Read for Decompressor<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut output = Vec::with_capacity(buf.len());
+        let mut steps = 0;
+        while output.len() < buf.len() {
+            self.stream.normalise();
+            if let Some(top) = self.stream.frames.last().filter(|top| top.literal) {
+                // only this repetition, the frames below may have to be decompressed
+                let count = (buf.len() - output.len()).min(top.data.len() - top.pos);
+                self.stream.take_into(count, &mut output);
+                continue;
+            }
+            match self.stream.peek_marker() {
+                Peeked::Marker(letters, repeats, length) => {
+                    steps += 1;
+                    if steps > self.stream.step_limit {
+                        if !output.is_empty() {
+                            // the next read fails, unless output appears after all
+                            break;
+                        }
+                        return Err(io::Error::other("decompression expands markers without producing any output"));
+                    }
+                    self.stream.expand_marker(letters, repeats, length, self.version == Version::V1);
+                },
+                _ => {
+                    let before = output.len();
+                    self.stream.take_into(1, &mut output);
+                    if output.len() == before {
+                        break;
+                    }
+                    steps = 0;
+                }
+            }
+        }
+        if output.is_empty() {
+            if let Some(err) = self.stream.error.take() {
+                return Err(err);
+            }
+        }
+        buf[..output.len()].copy_from_slice(&output);
+        Ok(output.len())
+    }
+}
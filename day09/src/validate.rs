@@ -0,0 +1,109 @@
+// checks compressed text for malformed markers, reporting every problem with its byte offset.
+//
+// Not every problem stops decompression: a '(' with digits that do not make a marker is
+// plain text to the decompressors, and whitespace in data is repeated like anything else.
+// Numbers that do not fit, data running past the end of the input and data ending
+// within a character do.
+//
+// In v2 the data of every marker is checked as well. A marker reaching beyond the data it is
+// repeated in is fine there, see stream.rs for how that is decompressed.
+
+use crate::stream::Version;
+use crate::{Marker, MarkerAt};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Problem {
+    // '(' and digits that do not make a marker, e.g. (3x) or (12x4
+    Malformed,
+    // a number that does not fit in usize
+    TooLarge,
+    // the data of a marker runs past the end of the input
+    PastEnd,
+    // whitespace in the data of a marker, the puzzle ignores whitespace
+    Whitespace,
+    // the data of a marker ends within a multi-byte character, markers count bytes
+    SplitsCharacter,
+    // only from decompress_v2: the output does not end or is longer than usize::MAX
+    TooLong
+}
+
+impl Problem {
+    pub fn stops_decompression(&self) -> bool {
+        matches!(self, Problem::TooLarge | Problem::PastEnd | Problem::SplitsCharacter | Problem::TooLong)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatError {
+    pub offset: usize,
+    pub problem: Problem,
+    pub message: String
+}
+
+impl std::fmt::Display for FormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "offset {}: {}", self.offset, self.message)
+    }
+}
+
+fn report(errors: &mut Vec<FormatError>, offset: usize, problem: Problem, message: String) {
+    errors.push(FormatError { offset, problem, message });
+}
+
+fn check(text: &[u8], start: usize, end: usize, version: Version, nested: bool, errors: &mut Vec<FormatError>) {
+    let mut pos = start;
+    while pos < end {
+        let marker = match Marker::at(text, pos, end) {
+            MarkerAt::Found(marker) => marker,
+            // plain text, as is a '(' without digits
+            MarkerAt::NotMarker | MarkerAt::Incomplete if text[pos] != b'(' || !text.get(pos + 1).is_some_and(|c| c.is_ascii_digit()) => {
+                pos += 1;
+                continue;
+            },
+            MarkerAt::NotMarker => {
+                report(errors, pos, Problem::Malformed, "'(' followed by digits is not a valid marker".to_string());
+                pos += 1;
+                continue;
+            },
+            MarkerAt::TooLarge => {
+                report(errors, pos, Problem::TooLarge, "number in marker does not fit in usize".to_string());
+                pos += 1;
+                continue;
+            },
+            // the next copy or the rest of the input may complete it
+            MarkerAt::Incomplete if nested => break,
+            MarkerAt::Incomplete => {
+                report(errors, pos, Problem::Malformed, "marker is cut off by the end of the input".to_string());
+                break;
+            }
+        };
+        let available = end - marker.endidx;
+        let data_end = if marker.letters > available {
+            if !nested {
+                report(errors, pos, Problem::PastEnd, format!("marker needs {} bytes of data, only {} left", marker.letters, available));
+            }
+            end
+        } else {
+            marker.endidx + marker.letters
+        };
+        // a utf-8 continuation byte right after the data
+        if !nested && text.get(data_end).is_some_and(|byte| byte & 0xc0 == 0x80) {
+            report(errors, pos, Problem::SplitsCharacter, format!("the data of the marker ends within a character at offset {}", data_end));
+        }
+        if !nested {
+            if let Some(space) = (marker.endidx..data_end).find(|index| text[*index].is_ascii_whitespace()) {
+                report(errors, space, Problem::Whitespace, format!("whitespace in the data of the marker at offset {}", pos));
+            }
+        }
+        if version == Version::V2 {
+            check(text, marker.endidx, data_end, version, true, errors);
+        }
+        pos = data_end;
+    }
+}
+
+pub fn validate(text: &str, version: Version) -> Vec<FormatError> {
+    let mut errors = Vec::new();
+    check(text.as_bytes(), 0, text.len(), version, false, &mut errors);
+    errors
+}
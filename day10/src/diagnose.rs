@@ -0,0 +1,138 @@
+// problems found in a factory, before or while simulating it.
+//
+// The simulation does not stop at any of them: a chip that has nowhere to go is dropped
+// and reported, and everything else runs as usual.
+
+use std::cmp::min;
+use std::collections::{HashMap, HashSet};
+
+use crate::{Destination, Instruction};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Diagnostic {
+    // a bot that gets chips, but no give rule
+    UndefinedBot(usize),
+    // a bot with several give rules, the last one is used
+    DuplicateRule(usize),
+    // bots giving chips to each other, sorted by id
+    Cycle(Vec<usize>),
+    // (bot, chip): a third chip for a bot still holding two, it is dropped
+    Overfull(usize, usize),
+    // (output, chip): a second chip for an output, it is dropped
+    OutputConflict(usize, usize),
+    // (bot, chip): a chip left with a bot, it never got a second one or had given already
+    Stuck(usize, usize)
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Diagnostic::UndefinedBot(bot) => write!(f, "bot {} is referenced but has no give rule", bot),
+            Diagnostic::DuplicateRule(bot) => write!(f, "bot {} has more than one give rule, the last one is used", bot),
+            Diagnostic::Cycle(bots) => {
+                let bots = bots.iter().map(|bot| bot.to_string()).collect::<Vec<_>>();
+                write!(f, "bots {} give chips to each other in a cycle", bots.join(", "))
+            },
+            Diagnostic::Overfull(bot, chip) => write!(f, "bot {} has its hands full, chip {} is dropped", bot, chip),
+            Diagnostic::OutputConflict(output, chip) => write!(f, "output {} already holds a chip, chip {} is dropped", output, chip),
+            Diagnostic::Stuck(bot, chip) => write!(f, "bot {} is stuck holding chip {}", bot, chip)
+        }
+    }
+}
+
+// strongly connected components of the give graph that contain a cycle.
+// Tarjan's algorithm, with an explicit stack since chains of bots can be long
+fn cycles(edges: &HashMap<usize, Vec<usize>>) -> Vec<Vec<usize>> {
+    let mut nodes = edges.keys().copied().collect::<Vec<_>>();
+    nodes.sort();
+    let mut index = HashMap::new();
+    let mut lowlink = HashMap::new();
+    let mut stack = Vec::new();
+    let mut on_stack = HashSet::new();
+    let mut result = Vec::new();
+    for root in nodes {
+        if index.contains_key(&root) {
+            continue;
+        }
+        // (node, index of the next edge to follow)
+        let mut work = vec![(root, 0)];
+        index.insert(root, index.len());
+        lowlink.insert(root, lowlink.len());
+        stack.push(root);
+        on_stack.insert(root);
+        while let Some((node, edge)) = work.last().copied() {
+            match edges.get(&node).and_then(|next| next.get(edge)).copied() {
+                Some(next) => {
+                    work.last_mut().unwrap().1 += 1;
+                    if !index.contains_key(&next) {
+                        index.insert(next, index.len());
+                        lowlink.insert(next, lowlink.len());
+                        stack.push(next);
+                        on_stack.insert(next);
+                        work.push((next, 0));
+                    } else if on_stack.contains(&next) {
+                        lowlink.insert(node, min(lowlink[&node], index[&next]));
+                    }
+                },
+                None => {
+                    work.pop();
+                    if let Some((parent, _)) = work.last() {
+                        lowlink.insert(*parent, min(lowlink[parent], lowlink[&node]));
+                    }
+                    if lowlink[&node] == index[&node] {
+                        let mut component = Vec::new();
+                        loop {
+                            let member = stack.pop().unwrap();
+                            on_stack.remove(&member);
+                            component.push(member);
+                            if member == node {
+                                break;
+                            }
+                        }
+                        if component.len() > 1 || edges.get(&node).is_some_and(|next| next.contains(&node)) {
+                            component.sort();
+                            result.push(component);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    result.sort();
+    result
+}
+
+// what can be found without simulating: undefined bots, duplicate rules and cycles
+pub fn check_instructions(instructions: &[Instruction]) -> Vec<Diagnostic> {
+    let mut rules = HashMap::new();
+    let mut referenced = Vec::new();
+    let mut edges: HashMap<usize, Vec<usize>> = HashMap::new();
+    for instruction in instructions {
+        match *instruction {
+            Instruction::ValueTo(_, Destination::Bot(bot)) => referenced.push(bot),
+            Instruction::ValueTo(_, Destination::Output(_)) => {},
+            Instruction::BotLowHigh(bot, lowdest, highdest) => {
+                *rules.entry(bot).or_insert(0) += 1;
+                let targets = edges.entry(bot).or_default();
+                for dest in [lowdest, highdest] {
+                    if let Destination::Bot(target) = dest {
+                        referenced.push(target);
+                        targets.push(target);
+                    }
+                }
+            }
+        }
+    }
+    let mut diagnostics = Vec::new();
+    let mut seen = HashSet::new();
+    for bot in referenced {
+        if !rules.contains_key(&bot) && seen.insert(bot) {
+            diagnostics.push(Diagnostic::UndefinedBot(bot));
+        }
+    }
+    let mut duplicates = rules.into_iter().filter(|(_, count)| *count > 1).map(|(bot, _)| bot).collect::<Vec<_>>();
+    duplicates.sort();
+    diagnostics.extend(duplicates.into_iter().map(Diagnostic::DuplicateRule));
+    diagnostics.extend(cycles(&edges).into_iter().map(Diagnostic::Cycle));
+    diagnostics
+}
@@ -0,0 +1,64 @@
+// Graphviz export of the bot network, render with e.g. `dot -Tsvg`.
+//
+// Bots are boxes and outputs ellipses, every give rule adds a low and a high edge.
+// The chips of the value instructions come in from plain text nodes. With a simulation,
+// the edges are labelled with the chips that went along them.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
+
+use crate::{Destination, Instruction, Simulation};
+
+fn node(dest: Destination) -> String {
+    match dest {
+        Destination::Bot(id) => format!("bot{}", id),
+        Destination::Output(id) => format!("output{}", id)
+    }
+}
+
+pub fn to_dot(instructions: &[Instruction], simulation: Option<&Simulation>) -> String {
+    // (low, high) per bot
+    let compared: HashMap<usize, (usize, usize)> = simulation
+        .map(|simulation| simulation.comparisons().into_iter().map(|(bot, low, high)| (bot, (low, high))).collect())
+        .unwrap_or_default();
+    let mut nodes = BTreeSet::new();
+    let mut edges = Vec::new();
+    for instruction in instructions {
+        match *instruction {
+            Instruction::ValueTo(value, dest) => {
+                nodes.insert(dest);
+                edges.push(format!("value{} -> {}", value, node(dest)));
+            },
+            Instruction::BotLowHigh(botid, lowdest, highdest) => {
+                nodes.extend([Destination::Bot(botid), lowdest, highdest]);
+                let chips = compared.get(&botid);
+                for (name, dest, chip) in [("low", lowdest, chips.map(|c| c.0)), ("high", highdest, chips.map(|c| c.1))] {
+                    let label = match chip {
+                        Some(chip) => format!("{} {}", name, chip),
+                        None => name.to_string()
+                    };
+                    let style = if name == "low" {", style=dashed"} else {""};
+                    edges.push(format!("bot{} -> {} [label=\"{}\"{}]", botid, node(dest), label, style));
+                }
+            }
+        }
+    }
+    let mut dot = String::from("digraph factory {\n    rankdir=LR;\n");
+    for dest in nodes {
+        let shape = match dest {
+            Destination::Bot(_) => "box",
+            Destination::Output(_) => "ellipse"
+        };
+        writeln!(dot, "    {} [label=\"{}\", shape={}];", node(dest), dest, shape).unwrap();
+    }
+    for instruction in instructions {
+        if let Instruction::ValueTo(value, _) = instruction {
+            writeln!(dot, "    value{} [label=\"{}\", shape=plaintext];", value, value).unwrap();
+        }
+    }
+    for edge in edges {
+        writeln!(dot, "    {};", edge).unwrap();
+    }
+    dot.push_str("}\n");
+    dot
+}
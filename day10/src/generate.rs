@@ -0,0 +1,78 @@
+// random factories for stress tests and benchmarks, always acyclic and solvable.
+//
+// The bots are created in a random order, each taking its two chips from value
+// instructions or from the low and high hands of bots created before it. That order is
+// a topological one, so there are no cycles, and every bot gets exactly two chips.
+// Whatever is left over goes to outputs, one chip each, so a factory has as many outputs
+// as chips. Chips that are not needed by any bot are dealt straight to outputs.
+
+use assembunny::fuzz::Rng;
+use crate::{Destination, Instruction};
+
+// a number below bound
+fn below(rng: &mut Rng, bound: usize) -> usize {
+    rng.range(0, bound as isize - 1) as usize
+}
+
+fn shuffle<T>(rng: &mut Rng, items: &mut [T]) {
+    for index in (1..items.len()).rev() {
+        items.swap(index, below(rng, index + 1));
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Config {
+    pub bots: usize,
+    pub chips: usize,
+    pub seed: u64
+}
+
+pub fn generate(config: Config) -> Result<Vec<Instruction>, String> {
+    if config.bots > 0 && config.chips < 2 {
+        return Err("bots need at least two chips to compare".to_string());
+    }
+    let mut rng = Rng::new(config.seed);
+    let mut bot_ids = (0..config.bots).collect::<Vec<_>>();
+    shuffle(&mut rng, &mut bot_ids);
+    let mut values = (1..=config.chips).collect::<Vec<_>>();
+    shuffle(&mut rng, &mut values);
+    let mut output_ids = (0..config.chips).collect::<Vec<_>>();
+    shuffle(&mut rng, &mut output_ids);
+
+    // chips dealt to bots, the first one needs two
+    let mut to_deal = if config.bots > 0 {2 + below(&mut rng, config.chips.min(2 * config.bots) - 1)} else {0};
+    let mut instructions = Vec::new();
+    // (low, high) per bot, in the order of bot_ids
+    let mut rules = vec![(None, None); config.bots];
+    // hands of earlier bots not given to anyone yet: (index into bot_ids, high)
+    let mut hands: Vec<(usize, bool)> = Vec::new();
+    for (index, bot) in bot_ids.iter().enumerate() {
+        // as many as the bots after this one can still take, the rest from free hands
+        let later = config.bots - index - 1;
+        let lowest = to_deal.saturating_sub(2 * later).max(2usize.saturating_sub(hands.len()));
+        let highest = to_deal.min(2);
+        let dealt = lowest + below(&mut rng, highest - lowest + 1);
+        for _ in 0..dealt {
+            instructions.push(Instruction::ValueTo(values.pop().unwrap(), Destination::Bot(*bot)));
+        }
+        to_deal -= dealt;
+        for _ in dealt..2 {
+            let (giver, high) = hands.swap_remove(below(&mut rng, hands.len()));
+            let dest = Some(Destination::Bot(*bot));
+            if high {rules[giver].1 = dest} else {rules[giver].0 = dest}
+        }
+        hands.extend([(index, false), (index, true)]);
+    }
+    for (giver, high) in hands {
+        let dest = Some(Destination::Output(output_ids.pop().unwrap()));
+        if high {rules[giver].1 = dest} else {rules[giver].0 = dest}
+    }
+    for value in values {
+        instructions.push(Instruction::ValueTo(value, Destination::Output(output_ids.pop().unwrap())));
+    }
+    for (bot, (low, high)) in bot_ids.iter().zip(rules) {
+        instructions.push(Instruction::BotLowHigh(*bot, low.unwrap(), high.unwrap()));
+    }
+    shuffle(&mut rng, &mut instructions);
+    Ok(instructions)
+}
@@ -5,12 +5,19 @@ use regex::Regex;
 use lazy_static::lazy_static;
 use std::cmp::{min, max};
 
+mod diagnose;
+mod dot;
+mod generate;
+mod solve;
+
+use diagnose::Diagnostic;
+
 lazy_static!{
     static ref VALUE_RE: Regex = Regex::new(r"value (?<value>\d+) goes to (?<destination>\w+ \d+)").unwrap();
     static ref BOT_RE: Regex = Regex::new(r"bot (?<botid>\d+) gives low to (?<lowdest>\w+ \d+) and high to (?<highdest>\w+ \d+)").unwrap();
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 enum Destination {
     Bot(usize),
     Output(usize)
@@ -28,12 +35,31 @@ impl From<&str> for Destination {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+impl std::fmt::Display for Destination {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Destination::Bot(id) => write!(f, "bot {}", id),
+            Destination::Output(id) => write!(f, "output {}", id)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Instruction {
     ValueTo(usize, Destination),
     BotLowHigh(usize, Destination, Destination)
 }
 
+// in the input format
+impl std::fmt::Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Instruction::ValueTo(value, dest) => write!(f, "value {} goes to {}", value, dest),
+            Instruction::BotLowHigh(botid, lowdest, highdest) => write!(f, "bot {} gives low to {} and high to {}", botid, lowdest, highdest)
+        }
+    }
+}
+
 impl From<&str> for Instruction {
     fn from(value: &str) -> Self {
         if value.starts_with("value") {
@@ -59,22 +85,26 @@ struct Bot {
     id: usize,
     left: Option<usize>,
     right: Option<usize>,
-    instruction: Option<Instruction>
+    instruction: Option<Instruction>,
+    // gave its chips away, a bot only does that once
+    gave: bool
 }
 
 impl Bot {
     fn new(id: usize) -> Bot {
-        Bot{id, left: None, right: None, instruction: None}
+        Bot{id, left: None, right: None, instruction: None, gave: false}
     }
 
-    fn add_chip(&mut self, value: usize) {
+    // false if all hands are full already
+    fn add_chip(&mut self, value: usize) -> bool {
         if self.left.is_none() {
             self.left = Some(value)
         } else if self.right.is_none() {
             self.right = Some(value)
         } else {
-            panic!("Got all hands full already");
+            return false;
         }
+        true
     }
 
     fn has_two_chips(&self) -> bool {
@@ -85,19 +115,22 @@ impl Bot {
 #[derive(Debug, Eq, PartialEq, Clone)]
 struct Output {
     id: usize,
-    content: Option<usize>
+    // more than one only if multi chip outputs are allowed
+    chips: Vec<usize>
 }
 
 impl Output {
     fn new(id: usize) -> Output {
-        Output{id, content: None}
+        Output{id, chips: Vec::new()}
     }
 
-    fn set_content(&mut self, content: usize) {
-        if self.content.is_some() {
-            panic!("Output already full");
+    // false if the output is full already
+    fn add_chip(&mut self, chip: usize, multi_chip: bool) -> bool {
+        if !self.chips.is_empty() && !multi_chip {
+            return false;
         }
-        self.content = Some(content);
+        self.chips.push(chip);
+        true
     }
 }
 
@@ -118,7 +151,74 @@ impl std::cmp::PartialEq for Bot {
 impl std::cmp::Eq for Bot {}
 
 
-fn simulate_process(instructions: &[Instruction], part2: bool) -> Option<usize> {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Event {
+    // a value instruction handed out a chip
+    Dealt(usize, Destination),
+    // (bot, low, high)
+    Compared(usize, usize, usize),
+    // (bot, chip, destination)
+    Gave(usize, usize, Destination)
+}
+
+// everything that happened, in order, and where the chips ended up
+#[derive(Debug)]
+struct Simulation {
+    events: Vec<Event>,
+    bots: HashMap<usize, Bot>,
+    outputs: HashMap<usize, Output>,
+    diagnostics: Vec<Diagnostic>
+}
+
+impl Simulation {
+    // (bot, low, high) for every comparison in order
+    fn comparisons(&self) -> Vec<(usize, usize, usize)> {
+        self.events.iter().filter_map(|event| match event {
+            Event::Compared(bot, low, high) => Some((*bot, *low, *high)),
+            _ => None
+        }).collect()
+    }
+
+    // the bot that compared the two chips, in any order
+    fn bot_comparing(&self, chip1: usize, chip2: usize) -> Option<usize> {
+        let (low, high) = (min(chip1, chip2), max(chip1, chip2));
+        self.comparisons().into_iter().find(|(_, l, h)| *l == low && *h == high).map(|(bot, _, _)| bot)
+    }
+
+    // the chip that ended up in an output, None if there is none or several
+    fn output(&self, id: usize) -> Option<usize> {
+        match self.output_chips(id) {
+            [chip] => Some(*chip),
+            _ => None
+        }
+    }
+
+    fn output_chips(&self, id: usize) -> &[usize] {
+        self.outputs.get(&id).map(|output| output.chips.as_slice()).unwrap_or(&[])
+    }
+}
+
+// hands a chip to a bot or an output, queueing the bot once it has two chips
+fn deliver(chip: usize, dest: Destination, bots: &mut HashMap<usize, Bot>, outputs: &mut HashMap<usize, Output>,
+           queue: &mut Vec<Bot>, diagnostics: &mut Vec<Diagnostic>, multi_chip_outputs: bool) {
+    match dest {
+        Destination::Bot(id) => {
+            let bot = bots.get_mut(&id).unwrap();
+            if !bot.add_chip(chip) {
+                diagnostics.push(Diagnostic::Overfull(id, chip));
+            } else if bot.has_two_chips() && !bot.gave {
+                queue.push(bot.clone());
+            }
+        },
+        Destination::Output(id) => {
+            if !outputs.get_mut(&id).unwrap().add_chip(chip, multi_chip_outputs) {
+                diagnostics.push(Diagnostic::OutputConflict(id, chip));
+            }
+        }
+    }
+}
+
+fn simulate_process(instructions: &[Instruction], multi_chip_outputs: bool) -> Simulation {
     let mut bots = HashMap::new();
     let mut outputs = HashMap::new();
 
@@ -141,6 +241,8 @@ fn simulate_process(instructions: &[Instruction], part2: bool) -> Option<usize>
         }
     };
     let mut queue = Vec::new();
+    let mut events = Vec::new();
+    let mut diagnostics = diagnose::check_instructions(instructions);
     // create all bots and outputs
     for instruction in instructions.iter() {
         match instruction {
@@ -159,82 +261,164 @@ fn simulate_process(instructions: &[Instruction], part2: bool) -> Option<usize>
     for instruction in instructions.iter() {
         match instruction {
             Instruction::ValueTo(value, dest) => {
-                match dest {
-                    Destination::Bot(id) => {
-                        // test if bot already exists
-                        // add value to bot
-                        let mut bot = bots.get_mut(id).unwrap();
-                        bot.add_chip(*value);
-                        if bot.has_two_chips() {
-                            queue.push(bot.clone());
-                        }
-                    },
-                    Destination::Output(id) => {
-                        // test if output already exists
-                        // add value to output
-                        let output = outputs.get_mut(id).unwrap();
-                        output.set_content(*value);
-                    }
-                }
+                events.push(Event::Dealt(*value, *dest));
+                deliver(*value, *dest, &mut bots, &mut outputs, &mut queue, &mut diagnostics, multi_chip_outputs);
             },
             _ => {}
         }
     }
 
-    'queueloop: while let Some(bot) = queue.pop() {
+    while let Some(bot) = queue.pop() {
         let left = bot.left.unwrap();
         let right = bot.right.unwrap();
         let (low, high) = if left < right {(left, right)} else {(right, left)};
-        if !part2 && low == 17 && high == 61 {
-            return Some(bot.id);
-        }
+        events.push(Event::Compared(bot.id, low, high));
         if let Some(Instruction::BotLowHigh(botid, lowdest, highdest)) = bot.instruction {
-            match lowdest {
-                Destination::Bot(botid) => {
-                    let mut lowbot = bots.get_mut(&botid).unwrap();
-                    lowbot.add_chip(low);
-                    if lowbot.has_two_chips() {
-                        queue.push(lowbot.clone());
-                    }
-                },
-                Destination::Output(outid) => outputs.get_mut(&outid).unwrap().set_content(low)
-            };
-            // DRY lost :(
-            match highdest {
-                Destination::Bot(botid) => {
-                    let mut lowbot = bots.get_mut(&botid).unwrap();
-                    lowbot.add_chip(high);
-                    if lowbot.has_two_chips() {
-                        queue.push(lowbot.clone());
-                    }
-                },
-                Destination::Output(outid) => outputs.get_mut(&outid).unwrap().set_content(high)
-            }
-        }
-        if part2 {
-            let mut result = 1;
-            for i in (0..3) {
-                if let Some(value) = outputs.get(&i).unwrap().content {
-                    result *= value;
-                } else {
-                    continue 'queueloop; 
-                }
-            }
-            return Some(result)
+            let giver = bots.get_mut(&bot.id).unwrap();
+            (giver.left, giver.right, giver.gave) = (None, None, true);
+            events.push(Event::Gave(bot.id, low, lowdest));
+            deliver(low, lowdest, &mut bots, &mut outputs, &mut queue, &mut diagnostics, multi_chip_outputs);
+            events.push(Event::Gave(bot.id, high, highdest));
+            deliver(high, highdest, &mut bots, &mut outputs, &mut queue, &mut diagnostics, multi_chip_outputs);
         }
     }
-    None
+    // one chip, or chips that came back after the bot gave
+    let mut stuck = bots.values()
+        .filter(|bot| bot.gave || !bot.has_two_chips())
+        .flat_map(|bot| [bot.left, bot.right].into_iter().flatten().map(|chip| (bot.id, chip)))
+        .collect::<Vec<_>>();
+    stuck.sort();
+    diagnostics.extend(stuck.into_iter().map(|(bot, chip)| Diagnostic::Stuck(bot, chip)));
+    Simulation { events, bots, outputs, diagnostics }
 }
 
 use std::collections::HashMap;
 fn part1(lines: &Vec<&str>) -> Option<usize> {
     let instructions: Vec<Instruction> = lines.iter().map(|l| Instruction::from(*l)).collect();
-    simulate_process(&instructions, false)
+    simulate_process(&instructions, false).bot_comparing(17, 61)
 }
 
 fn part2(lines: &Vec<&str>) -> Option<usize> {
     let instructions: Vec<Instruction> = lines.iter().map(|l| Instruction::from(*l)).collect();
-    simulate_process(&instructions, true)
+    let simulation = simulate_process(&instructions, false);
+    (0..3).map(|id| simulation.output(id)).product()
+}
+
+fn instructions_arg(path: Option<&String>) -> Vec<Instruction> {
+    let path = path.unwrap_or_else(|| {
+        println!("Missing argument, run without arguments for usage");
+        std::process::exit(1);
+    });
+    let contents = std::fs::read_to_string(path).expect("Could not read in file");
+    contents.lines().map(Instruction::from).collect()
+}
+
+fn number_arg(arg: Option<&String>) -> usize {
+    arg.and_then(|a| a.parse().ok()).unwrap_or_else(|| {
+        println!("Expected a number, run without arguments for usage");
+        std::process::exit(1);
+    })
+}
+
+// answers a question about the simulation of the input:
+// --compares <chip> <chip>, --output <id> or --trace
+fn query_main(args: &[String]) {
+    // the input follows the numbers of the query
+    let numbers = match args[1].as_str() {"--trace" => 0, "--output" => 1, _ => 2};
+    let simulation = simulate_process(&instructions_arg(args.get(2 + numbers)), false);
+    match args[1].as_str() {
+        "--trace" => {
+            for event in &simulation.events {
+                match event {
+                    Event::Dealt(value, dest) => println!("value {} goes to {}", value, dest),
+                    Event::Compared(bot, low, high) => println!("bot {} compares {} with {}", bot, low, high),
+                    Event::Gave(bot, value, dest) => println!("bot {} gives {} to {}", bot, value, dest)
+                }
+            }
+        },
+        "--output" => match simulation.output(number_arg(args.get(2))) {
+            Some(value) => println!("{}", value),
+            None => println!("Nothing ends up in that output")
+        },
+        _ => match simulation.bot_comparing(number_arg(args.get(2)), number_arg(args.get(3))) {
+            Some(bot) => println!("bot {}", bot),
+            None => println!("No bot compares these chips")
+        }
+    }
+}
+
+
+// prints the network as a graphviz digraph, with --chips labelled with the chips that went along
+fn dot_main(args: &[String]) {
+    let chips = args.get(2).is_some_and(|a| a == "--chips");
+    let instructions = instructions_arg(args.get(if chips {3} else {2}));
+    let simulation = if chips {Some(simulate_process(&instructions, false))} else {None};
+    print!("{}", dot::to_dot(&instructions, simulation.as_ref()));
+}
+
+// lists the problems of the input, with --multi outputs may take several chips
+fn check_main(args: &[String]) {
+    let multi = args.get(2).is_some_and(|a| a == "--multi");
+    let simulation = simulate_process(&instructions_arg(args.get(if multi {3} else {2})), multi);
+    for diagnostic in &simulation.diagnostics {
+        println!("{}", diagnostic);
+    }
+    if !simulation.diagnostics.is_empty() {
+        std::process::exit(1);
+    }
+    println!("No problems found");
+}
+
+// times the simulation against the static solver and checks that they agree, on the input
+// or with --generated on a random factory with the given number of bots and chips
+fn bench_main(args: &[String]) {
+    use std::time::Instant;
+    let instructions = if args.get(2).is_some_and(|a| a == "--generated") {
+        let config = generate::Config {
+            bots: number_arg(args.get(3)),
+            chips: number_arg(args.get(4)),
+            seed: args.get(5).map_or(1, |a| a.parse().expect("The seed must be a number"))
+        };
+        generate::generate(config).unwrap_or_else(|err| {
+            println!("{}", err);
+            std::process::exit(1);
+        })
+    } else {
+        instructions_arg(args.get(2))
+    };
+    let start = Instant::now();
+    let simulation = simulate_process(&instructions, false);
+    println!("Simulation: {:?}", start.elapsed());
+    let start = Instant::now();
+    let solution = solve::solve(&instructions);
+    println!("Solver: {:?}", start.elapsed());
+    match solution {
+        Ok(solution) if solution.matches(&simulation) => println!("Both agree on {} bots and {} outputs", solution.bots.len(), solution.outputs.len()),
+        Ok(_) => {
+            println!("The solver and the simulation disagree");
+            std::process::exit(1);
+        },
+        Err(err) => {
+            println!("The solver failed: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+// prints a random factory with the given number of bots and chips, each chip ends up in its own output
+fn generate_main(args: &[String]) {
+    let config = generate::Config {
+        bots: number_arg(args.get(2)),
+        chips: number_arg(args.get(3)),
+        seed: args.get(4).map_or(1, |a| a.parse().expect("The seed must be a number"))
+    };
+    match generate::generate(config) {
+        Ok(instructions) => instructions.iter().for_each(|instruction| println!("{}", instruction)),
+        Err(err) => {
+            println!("{}", err);
+            std::process::exit(1);
+        }
+    }
 }
 
 fn main() {
@@ -242,8 +426,36 @@ fn main() {
     use std::env;
     use std::time::Instant;
     let args: Vec<String> =  env::args().collect();
+    if args.len() > 1 && ["--compares", "--output", "--trace"].contains(&args[1].as_str()) {
+        query_main(&args);
+        return;
+    }
+    if args.len() > 1 && args[1] == "--check" {
+        check_main(&args);
+        return;
+    }
+    if args.len() > 1 && args[1] == "--generate" {
+        generate_main(&args);
+        return;
+    }
+    if args.len() > 1 && args[1] == "--bench" {
+        bench_main(&args);
+        return;
+    }
+    if args.len() > 1 && args[1] == "--dot" {
+        dot_main(&args);
+        return;
+    }
     let infile = args.get(1).unwrap_or_else(|| {
         println!("Usage: {} <puzzle input>", args[0]);
+        println!("       {} --compares <chip> <chip> <puzzle input>", args[0]);
+        println!("       {} --output <id> <puzzle input>", args[0]);
+        println!("       {} --trace <puzzle input>", args[0]);
+        println!("       {} --dot [--chips] <puzzle input>", args[0]);
+        println!("       {} --check [--multi] <puzzle input>", args[0]);
+        println!("       {} --bench <puzzle input>", args[0]);
+        println!("       {} --bench --generated <bots> <chips> [seed]", args[0]);
+        println!("       {} --generate <bots> <chips> [seed]", args[0]);
         std::process::exit(1);
     });
 
@@ -281,4 +493,215 @@ mod tests {
         let lines: Vec<&str> = TESTINPUT.lines().collect();
         assert_eq!(Some(13337), part2(&lines));
     }
+
+    static EXAMPLE: &str = "value 5 goes to bot 2
+bot 2 gives low to bot 1 and high to bot 0
+value 3 goes to bot 1
+bot 1 gives low to output 1 and high to bot 0
+bot 0 gives low to output 2 and high to output 0
+value 2 goes to bot 2";
+
+    fn example() -> Vec<Instruction> {
+        EXAMPLE.lines().map(Instruction::from).collect()
+    }
+
+    #[test]
+    fn test_queries() {
+        let simulation = simulate_process(&example(), false);
+        assert_eq!(vec![(2, 2, 5), (1, 2, 3), (0, 3, 5)], simulation.comparisons());
+        assert_eq!(Some(2), simulation.bot_comparing(5, 2));
+        assert_eq!(Some(0), simulation.bot_comparing(3, 5));
+        assert_eq!(None, simulation.bot_comparing(17, 61));
+        assert_eq!([Some(5), Some(2), Some(3), None], [0, 1, 2, 3].map(|id| simulation.output(id)));
+        assert_eq!(Some(30), part2(&EXAMPLE.lines().collect()));
+    }
+
+    #[test]
+    fn test_event_log() {
+        let simulation = simulate_process(&example(), false);
+        // three chips dealt, each of the three bots compares and gives twice
+        assert_eq!(12, simulation.events.len());
+        assert_eq!(Event::Dealt(5, Destination::Bot(2)), simulation.events[0]);
+        assert_eq!(&[Event::Compared(2, 2, 5), Event::Gave(2, 2, Destination::Bot(1)), Event::Gave(2, 5, Destination::Bot(0))], &simulation.events[3..6]);
+        assert_eq!(Event::Gave(0, 5, Destination::Output(0)), simulation.events[11]);
+    }
+
+    #[test]
+    fn test_dot() {
+        let plain = dot::to_dot(&example(), None);
+        assert!(plain.starts_with("digraph factory {\n"));
+        assert!(plain.contains("    bot2 [label=\"bot 2\", shape=box];\n"));
+        assert!(plain.contains("    output1 [label=\"output 1\", shape=ellipse];\n"));
+        assert!(plain.contains("    value5 -> bot2;\n"));
+        assert!(plain.contains("    bot1 -> output1 [label=\"low\", style=dashed];\n"));
+        assert!(plain.contains("    bot1 -> bot0 [label=\"high\"];\n"));
+        // 3 bots, 3 outputs, 3 values, 3 dealt and 6 given chips
+        assert_eq!(2 + 18 + 1, plain.lines().count());
+        let chips = dot::to_dot(&example(), Some(&simulate_process(&example(), false)));
+        assert!(chips.contains("    bot1 -> output1 [label=\"low 2\", style=dashed];\n"));
+        assert!(chips.contains("    bot0 -> output0 [label=\"high 5\"];\n"));
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let simulate = |text: &str, multi: bool| {
+            simulate_process(&text.lines().map(Instruction::from).collect::<Vec<_>>(), multi)
+        };
+        assert!(simulate(EXAMPLE, false).diagnostics.is_empty());
+        // bot 1 never gets a second chip, bots 1 and 3 have no rule, bot 2 has two
+        let broken = "value 5 goes to bot 2
+value 7 goes to bot 2
+bot 2 gives low to bot 1 and high to bot 3
+bot 2 gives low to bot 1 and high to output 0
+value 1 goes to output 0";
+        let simulation = simulate(broken, false);
+        assert_eq!(vec![
+            Diagnostic::UndefinedBot(1),
+            Diagnostic::UndefinedBot(3),
+            Diagnostic::DuplicateRule(2),
+            Diagnostic::OutputConflict(0, 7),
+            Diagnostic::Stuck(1, 5)
+        ], simulation.diagnostics);
+        assert_eq!(Some(1), simulation.output(0));
+        // allowed, output 0 gets both chips
+        let simulation = simulate(broken, true);
+        assert_eq!(&[1, 7], simulation.output_chips(0));
+        assert_eq!(None, simulation.output(0));
+        assert!(!simulation.diagnostics.contains(&Diagnostic::OutputConflict(0, 7)));
+        // bots 0 and 1 feed each other, bot 0 gets a chip back after giving its chips away
+        let cycle = "value 1 goes to bot 0
+value 2 goes to bot 0
+value 3 goes to bot 1
+bot 0 gives low to bot 1 and high to bot 2
+bot 1 gives low to bot 0 and high to output 0
+bot 2 gives low to bot 2 and high to output 1";
+        let simulation = simulate(cycle, false);
+        assert_eq!(vec![
+            Diagnostic::Cycle(vec![0, 1]),
+            Diagnostic::Cycle(vec![2]),
+            Diagnostic::Stuck(0, 1),
+            Diagnostic::Stuck(2, 2)
+        ], simulation.diagnostics);
+        assert_eq!("bots 0, 1 give chips to each other in a cycle", simulation.diagnostics[0].to_string());
+        // both chips come back, bot 0 holds them and does not give again
+        let simulation = simulate("value 1 goes to bot 0
+value 2 goes to bot 0
+bot 0 gives low to bot 1 and high to bot 1
+bot 1 gives low to bot 0 and high to bot 0", false);
+        assert_eq!(vec![Diagnostic::Cycle(vec![0, 1]), Diagnostic::Stuck(0, 1), Diagnostic::Stuck(0, 2)], simulation.diagnostics);
+        assert_eq!(vec![(0, 1, 2), (1, 1, 2)], simulation.comparisons());
+        // a third chip for a bot that still holds two
+        let simulation = simulate("value 1 goes to bot 0
+value 2 goes to bot 0
+value 3 goes to bot 0
+bot 0 gives low to output 0 and high to output 1", false);
+        assert_eq!(vec![Diagnostic::Overfull(0, 3)], simulation.diagnostics);
+    }
+
+    #[test]
+    fn test_solve() {
+        let solution = solve::solve(&example()).unwrap();
+        assert_eq!(HashMap::from([(0, (3, 5)), (1, (2, 3)), (2, (2, 5))]), solution.bots);
+        assert_eq!(HashMap::from([(0, 5), (1, 2), (2, 3)]), solution.outputs);
+        assert!(solution.matches(&simulate_process(&example(), false)));
+    }
+
+    #[test]
+    fn test_solve_long_chain() {
+        // bot i keeps passing the higher chip on to bot i + 1, which gets another one dealt
+        let bots = 20000;
+        let mut instructions = vec![Instruction::ValueTo(0, Destination::Bot(0))];
+        for bot in 0..bots {
+            instructions.push(Instruction::ValueTo(bot + 1, Destination::Bot(bot)));
+            let high = if bot + 1 < bots {Destination::Bot(bot + 1)} else {Destination::Output(bots)};
+            instructions.push(Instruction::BotLowHigh(bot, Destination::Output(bot), high));
+        }
+        let solution = solve::solve(&instructions).unwrap();
+        assert_eq!(Some(&(bots - 1, bots)), solution.bots.get(&(bots - 1)));
+        assert!(solution.matches(&simulate_process(&instructions, false)));
+    }
+
+    #[test]
+    fn test_solve_idle_bots() {
+        // bots 1 and 3 never get a chip
+        let text = "value 1 goes to bot 0
+value 2 goes to bot 0
+bot 0 gives low to output 0 and high to output 1
+bot 1 gives low to output 2 and high to bot 3
+bot 3 gives low to output 3 and high to output 4";
+        let instructions = text.lines().map(Instruction::from).collect::<Vec<_>>();
+        let solution = solve::solve(&instructions).unwrap();
+        assert_eq!(HashMap::from([(0, (1, 2))]), solution.bots);
+        assert_eq!(HashMap::from([(0, 1), (1, 2)]), solution.outputs);
+        let simulation = simulate_process(&instructions, false);
+        assert!(solution.matches(&simulation));
+        assert!(simulation.diagnostics.is_empty());
+        // an idle bot passing nothing on leaves another one with a single chip, both report it
+        let text = "value 1 goes to bot 2
+bot 1 gives low to output 0 and high to bot 2
+bot 2 gives low to output 1 and high to output 2";
+        let instructions = text.lines().map(Instruction::from).collect::<Vec<_>>();
+        assert_eq!("bot 2 ends up with 1 chips instead of two", solve::solve(&instructions).unwrap_err());
+        assert_eq!(vec![Diagnostic::Stuck(2, 1)], simulate_process(&instructions, false).diagnostics);
+    }
+
+    #[test]
+    fn test_solve_errors() {
+        let solve = |text: &str| solve::solve(&text.lines().map(Instruction::from).collect::<Vec<_>>()).unwrap_err();
+        assert_eq!("bots [0, 1] are in a cycle or get chips from one", solve("value 1 goes to bot 0
+value 2 goes to bot 1
+bot 0 gives low to bot 1 and high to output 0
+bot 1 gives low to bot 0 and high to output 1"));
+        assert_eq!("bot 0 ends up with 1 chips instead of two", solve("value 1 goes to bot 0
+bot 0 gives low to output 0 and high to output 1"));
+        assert_eq!("bot 1 gets chips but has no give rule", solve("value 1 goes to bot 1"));
+        assert_eq!("output 0 gets more than one chip", solve("value 1 goes to output 0
+value 2 goes to output 0"));
+    }
+
+    #[test]
+    fn test_generate() {
+        for seed in 1..200 {
+            let chips = 2 + (seed as usize * 13) % 120;
+            let config = generate::Config { bots: (seed as usize * 7) % 50, chips, seed };
+            let instructions = generate::generate(config).unwrap();
+            // written out and parsed back
+            let text = instructions.iter().map(|instruction| instruction.to_string()).collect::<Vec<_>>().join("\n");
+            let parsed = text.lines().map(Instruction::from).collect::<Vec<_>>();
+            assert_eq!(instructions, parsed);
+            assert_eq!(config.bots + config.chips, parsed.len());
+            let simulation = simulate_process(&parsed, false);
+            assert!(simulation.diagnostics.is_empty(), "{:?} for {:?}", simulation.diagnostics, config);
+            assert_eq!(config.bots, simulation.comparisons().len());
+            assert_eq!(config.chips, (0..config.chips).filter(|id| simulation.output(*id).is_some()).count());
+            assert!(solve::solve(&parsed).unwrap().matches(&simulation));
+        }
+        assert!(generate::generate(generate::Config { bots: 3, chips: 1, seed: 1 }).is_err());
+        assert_eq!(Ok(vec![]), generate::generate(generate::Config { bots: 0, chips: 0, seed: 1 }));
+    }
+
+    #[test]
+    fn test_generate_large() {
+        let instructions = generate::generate(generate::Config { bots: 50000, chips: 60000, seed: 42 }).unwrap();
+        let solution = solve::solve(&instructions).unwrap();
+        assert_eq!(50000, solution.bots.len());
+        assert!(solution.matches(&simulate_process(&instructions, false)));
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_simulation_vs_solver() {
+        use std::time::Instant;
+        for bots in [10000, 100000, 500000] {
+            let instructions = generate::generate(generate::Config { bots, chips: bots + bots / 5, seed: 7 }).unwrap();
+            let start = Instant::now();
+            let simulation = simulate_process(&instructions, false);
+            let simulation_time = start.elapsed();
+            let start = Instant::now();
+            let solution = solve::solve(&instructions).unwrap();
+            let solver_time = start.elapsed();
+            assert!(solution.matches(&simulation));
+            println!("{} bots: simulation: {:?}, solver: {:?}", bots, simulation_time, solver_time);
+        }
+    }
 }
@@ -0,0 +1,101 @@
+// solves a factory without simulating it chip by chip.
+//
+// Every bot has one give rule, so the give rules form a graph in which a bot can pass on
+// its chips as soon as all the bots giving to it have done so. Going through the bots in
+// that (topological) order, every bot holds its final two chips when we get to it.
+//
+// Only for well formed factories: the first problem found is returned as an error,
+// the simulation with its diagnostics tells all of them. Bots that never get a chip
+// are fine, they stay idle like in the simulation.
+
+use std::cmp::{max, min};
+use std::collections::{HashMap, HashSet};
+
+use crate::{Destination, Instruction, Simulation};
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Solution {
+    // (low, high) compared by every bot
+    pub bots: HashMap<usize, (usize, usize)>,
+    pub outputs: HashMap<usize, usize>
+}
+
+impl Solution {
+    // the same comparisons and outputs as a simulation
+    pub fn matches(&self, simulation: &Simulation) -> bool {
+        let compared = simulation.comparisons().into_iter().map(|(bot, low, high)| (bot, (low, high))).collect::<HashMap<_, _>>();
+        let outputs = simulation.outputs.keys().filter_map(|id| Some((*id, simulation.output(*id)?))).collect::<HashMap<_, _>>();
+        compared == self.bots && outputs == self.outputs
+    }
+}
+
+fn put_output(outputs: &mut HashMap<usize, usize>, id: usize, chip: usize) -> Result<(), String> {
+    match outputs.insert(id, chip) {
+        Some(_) => Err(format!("output {} gets more than one chip", id)),
+        None => Ok(())
+    }
+}
+
+pub fn solve(instructions: &[Instruction]) -> Result<Solution, String> {
+    let mut rules = HashMap::new();
+    let mut chips: HashMap<usize, Vec<usize>> = HashMap::new();
+    // number of bots that still have to give to a bot
+    let mut waiting: HashMap<usize, usize> = HashMap::new();
+    let mut solution = Solution::default();
+    for instruction in instructions {
+        match *instruction {
+            Instruction::ValueTo(chip, Destination::Bot(bot)) => chips.entry(bot).or_default().push(chip),
+            Instruction::ValueTo(chip, Destination::Output(id)) => put_output(&mut solution.outputs, id, chip)?,
+            Instruction::BotLowHigh(bot, lowdest, highdest) => {
+                if rules.insert(bot, (lowdest, highdest)).is_some() {
+                    return Err(format!("bot {} has more than one give rule", bot));
+                }
+                for dest in [lowdest, highdest] {
+                    if let Destination::Bot(target) = dest {
+                        *waiting.entry(target).or_insert(0) += 1;
+                    }
+                }
+            }
+        }
+    }
+    if let Some(bot) = chips.keys().chain(waiting.keys()).find(|bot| !rules.contains_key(bot)) {
+        return Err(format!("bot {} gets chips but has no give rule", bot));
+    }
+    let mut ready = rules.keys().filter(|bot| !waiting.contains_key(bot)).copied().collect::<Vec<_>>();
+    let mut done = HashSet::new();
+    while let Some(bot) = ready.pop() {
+        done.insert(bot);
+        let held = chips.remove(&bot).unwrap_or_default();
+        let (lowdest, highdest) = rules[&bot];
+        // a bot without chips stays idle, as in the simulation, and gives nothing
+        let given = match held[..] {
+            [] => vec![(None, lowdest), (None, highdest)],
+            [first, second] => {
+                let (low, high) = (min(first, second), max(first, second));
+                solution.bots.insert(bot, (low, high));
+                vec![(Some(low), lowdest), (Some(high), highdest)]
+            },
+            _ => return Err(format!("bot {} ends up with {} chips instead of two", bot, held.len()))
+        };
+        for (chip, dest) in given {
+            match (chip, dest) {
+                (_, Destination::Bot(target)) => {
+                    chips.entry(target).or_default().extend(chip);
+                    let count = waiting.get_mut(&target).unwrap();
+                    *count -= 1;
+                    if *count == 0 {
+                        ready.push(target);
+                    }
+                },
+                (Some(chip), Destination::Output(id)) => put_output(&mut solution.outputs, id, chip)?,
+                (None, Destination::Output(_)) => {}
+            }
+        }
+    }
+    if done.len() < rules.len() {
+        let mut left = rules.keys().filter(|bot| !done.contains(bot)).copied().collect::<Vec<_>>();
+        left.sort();
+        return Err(format!("bots {:?} are in a cycle or get chips from one", left));
+    }
+    Ok(solution)
+}
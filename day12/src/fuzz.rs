@@ -0,0 +1,28 @@
+// the implementations of this crate for the shared fuzzer, see assembunny::fuzz.
+
+use assembunny::fuzz::{Implementation, Outcome, Regs, STEP_LIMIT};
+use assembunny::{Instruction, InstructionSet};
+
+use crate::execute;
+
+fn interpreter(program: &[Instruction], regs: Regs, checked: bool) -> Option<Outcome> {
+    let mut steps = 0;
+    let (regs, _) = execute(program, regs, checked, |_, _| {
+        steps += 1;
+        steps < STEP_LIMIT
+    }).ok()?;
+    Some(Outcome { regs, steps, output: Vec::new() })
+}
+
+fn unchecked_interpreter(program: &[Instruction], regs: Regs) -> Option<Outcome> {
+    interpreter(program, regs, false)
+}
+
+fn checked_interpreter(program: &[Instruction], regs: Regs) -> Option<Outcome> {
+    interpreter(program, regs, true)
+}
+
+pub const IMPLEMENTATIONS: &[Implementation] = &[
+    Implementation { name: "day12 interpreter", instructions: InstructionSet::Basic, run: unchecked_interpreter },
+    Implementation { name: "day12 checked interpreter", instructions: InstructionSet::Basic, run: checked_interpreter }
+];
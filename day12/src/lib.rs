@@ -0,0 +1,94 @@
+#![allow(unused)]
+#![allow(dead_code)]
+
+// the interpreter, a library so that other days can compare theirs with it
+
+use assembunny::{add, IValue, Instruction, InstructionSet, LoopDetector, Overflow, RunOutcome};
+
+pub mod fuzz;
+
+// the instructions of this day's puzzle
+pub const INSTRUCTIONS: InstructionSet = InstructionSet::Basic;
+
+pub fn parse_program(lines: &[&str], num_registers: usize) -> Result<Vec<Instruction>, String> {
+    assembunny::parse_program(lines, num_registers, INSTRUCTIONS)
+}
+
+// observe is called after every executed instruction with its ip and the registers after it,
+// execution stops early once it returns false. Returns the registers and the next ip,
+// which is past the end of the program once it halted
+pub fn execute<const N: usize, F>(instructions: &[Instruction], regs: [isize; N], checked: bool, mut observe: F) -> Result<([isize; N], usize), Overflow>
+where F: FnMut(usize, &[isize; N]) -> bool {
+    let mut regs = regs;
+    let mut ip = 0;
+    let resolve = |val: &IValue, regs: &[isize; N]| -> isize {
+        match val {
+            IValue::Literal(intval) => *intval,
+            IValue::Register(index) => regs[*index as usize]
+        }
+    };
+    while (ip < instructions.len()) {
+        let current = ip;
+        let instruction = instructions[current];
+        let overflow = move || Overflow { ip: current, instruction };
+        ip += 1;
+        match instruction {
+            Instruction::Cpy(src, dest) => {
+                let srcval = resolve(&src, &regs);
+                regs[dest.unwrap() as usize] = srcval;
+            },
+            Instruction::Inc(dest) => {
+                let dest = dest.unwrap() as usize;
+                regs[dest] = add(regs[dest], 1, checked).ok_or_else(overflow)?;
+            },
+            Instruction::Dec(dest) => {
+                let dest = dest.unwrap() as usize;
+                regs[dest] = add(regs[dest], -1, checked).ok_or_else(overflow)?;
+            },
+            Instruction::Jnz(src, jmprange) => {
+                let srcval = resolve(&src, &regs);
+                if srcval != 0 {
+                    ip = (add(ip as isize - 1, resolve(&jmprange, &regs), checked).ok_or_else(overflow)?) as usize;
+                }
+            },
+            Instruction::Tgl(_) | Instruction::Out(_) => unreachable!("{:?} is not part of day 12, parse_program rejects it", instruction)
+        }
+        if !observe(current, &regs) {
+            break;
+        }
+    }
+    Ok((regs, ip))
+}
+
+pub fn run<const N: usize>(instructions: &[Instruction], regs: [isize; N]) -> [isize; N] {
+    execute(instructions, regs, false, |_, _| true).unwrap_or_else(|_| unreachable!("unchecked execution never traps")).0
+}
+
+// like run, but traps on overflow with the offending ip and instruction
+pub fn run_checked<const N: usize>(instructions: &[Instruction], regs: [isize; N]) -> Result<[isize; N], Overflow> {
+    execute(instructions, regs, true, |_, _| true).map(|(regs, _)| regs)
+}
+
+// like run, but gives up once the program needs more than budget steps
+// or provably runs forever
+pub fn run_with_budget<const N: usize>(instructions: &[Instruction], regs: [isize; N], budget: usize) -> RunOutcome<N> {
+    if budget == 0 {
+        return if instructions.is_empty() {RunOutcome::Halted(regs)} else {RunOutcome::BudgetExceeded(regs)};
+    }
+    let mut steps = 0;
+    let mut detector = LoopDetector::new();
+    let mut looped = false;
+    // the executed ip and the registers after it determine the rest of the run
+    let (regs, ip) = execute(instructions, regs, false, |ip, regs| {
+        steps += 1;
+        looped = detector.looped(ip, regs, instructions);
+        !looped && steps < budget
+    }).unwrap_or_else(|_| unreachable!("unchecked execution never traps"));
+    if looped {
+        RunOutcome::Looped(regs)
+    } else if ip < instructions.len() {
+        RunOutcome::BudgetExceeded(regs)
+    } else {
+        RunOutcome::Halted(regs)
+    }
+}
@@ -1,93 +1,18 @@
 #![allow(unused)]
 #![allow(dead_code)]
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum IValue {
-    Register(u8),
-    Literal(isize)
-}
-
-impl IValue {
-    fn unwrap(&self) -> isize {
-        match self {
-            IValue::Literal(intval) => *intval as isize,
-            IValue::Register(index) => *index as isize
-        }
-    }
-}
-
-enum Instruction {
-    Cpy(IValue, IValue),
-    Inc(IValue),
-    Dec(IValue),
-    Jnz(IValue, IValue)
-}
-
-impl From<&str> for Instruction {
-    fn from(line: &str) -> Self {
-        let (name, args) = line.split_once(' ').unwrap();
-        let arg_to_value = |s: &str| -> IValue {
-            if s.chars().all(|c| c == '-' || c.is_numeric()) {
-                IValue::Literal(s.parse().unwrap())
-            } else {
-                assert!(s.len() == 1);
-                IValue::Register(s.bytes().next().unwrap() as u8 - 'a' as u8)
-            }
-        };
-        let args = args.split_whitespace().map(arg_to_value).collect::<Vec<_>>();
-        match name.to_lowercase().as_str() {
-            "cpy" => Instruction::Cpy(args[0], args[1]),
-            "inc" => Instruction::Inc(args[0]),
-            "dec" => Instruction::Dec(args[0]),
-            "jnz" => Instruction::Jnz(args[0], args[1]),
-            _ => panic!("Unknown instruction")
-        }
-    }
-}
-
-fn run(instructions: &[Instruction], regs: [isize; 4]) -> [isize; 4] {
-    let mut regs = regs;
-    let mut ip = 0;
-    let resolve = |val: &IValue, regs: &[isize; 4]| -> isize {
-        match val {
-            IValue::Literal(intval) => *intval,
-            IValue::Register(index) => regs[*index as usize]
-        }
-    };
-    while (ip < instructions.len()) {
-        let instruction = &instructions[ip];
-        ip += 1;
-        match instruction {
-            Instruction::Cpy(src, dest) => {
-                let srcval = resolve(src, &regs);
-                regs[dest.unwrap() as usize] = srcval;
-            },
-            Instruction::Inc(dest) => {
-                regs[dest.unwrap() as usize] += 1;
-            },
-            Instruction::Dec(dest) => {
-                regs[dest.unwrap() as usize] -= 1;
-            },
-            Instruction::Jnz(src, jmprange) => {
-                let srcval = resolve(src, &regs);
-                if srcval != 0 {
-                    ip = (ip as isize + resolve(jmprange, &regs) - 1) as usize;
-                }
-            }
-        }
-    }
-    regs
-}
+use assembunny::{IValue, Instruction, Overflow, RunOutcome, NUM_REGISTERS};
+use day12::*;
 
 fn part1(lines: &Vec<&str>) -> Option<usize> {
-    let instructions = lines.iter().map(|l| Instruction::from(*l)).collect::<Vec<_>>();
+    let instructions = parse_program(lines, NUM_REGISTERS).unwrap_or_else(|err| panic!("{}", err));
     let mut regs = [0isize; 4];
     let regs = run(&instructions, regs);
     Some(regs[0] as usize)
 }
 
 fn part2(lines: &Vec<&str>) -> Option<usize> {
-    let instructions = lines.iter().map(|l| Instruction::from(*l)).collect::<Vec<_>>();
+    let instructions = parse_program(lines, NUM_REGISTERS).unwrap_or_else(|err| panic!("{}", err));
     let mut regs = [0isize; 4];
     regs[2] = 1;
     let regs = run(&instructions, regs);
@@ -133,6 +58,50 @@ mod tests {
         assert_eq!(Some(1337), part1(&lines));
     }
 
+    #[test]
+    fn test_parse_basic_instructions_only() {
+        assert!(Instruction::parse("cpy 1 2", 4, INSTRUCTIONS).unwrap_err().contains("literal"));
+        assert!(Instruction::parse("inc 1", 4, INSTRUCTIONS).unwrap_err().contains("literal"));
+        assert_eq!("line 2: unknown instruction 'tgl'", parse_program(&["inc a", "tgl a"], 4).unwrap_err());
+        assert!(parse_program(&["out a"], 4).unwrap_err().contains("unknown instruction 'out'"));
+    }
+
+    #[test]
+    fn test_more_registers() {
+        let instructions = parse_program(&["cpy 5 e", "inc a", "dec e", "jnz e -2"], 5).unwrap();
+        assert_eq!([5, 0, 0, 0, 0], run(&instructions, [0isize; 5]));
+    }
+
+    #[test]
+    fn test_checked_overflow() {
+        let instructions = parse_program(&["dec b", "inc a", "inc a"], 4).unwrap();
+        let overflow = run_checked(&instructions, [isize::MAX - 1, 0, 0, 0]).unwrap_err();
+        assert_eq!(Overflow { ip: 2, instruction: Instruction::Inc(IValue::Register(0)) }, overflow);
+        assert_eq!(Ok([isize::MAX, -1, 0, 0]), run_checked(&instructions[..2], [isize::MAX - 1, 0, 0, 0]));
+    }
+
+    #[test]
+    fn test_fuzz_implementations_agree() {
+        assembunny::fuzz::check(fuzz::IMPLEMENTATIONS, 12, 3000);
+    }
+
+    #[test]
+    fn test_run_with_budget() {
+        assembunny::fuzz::check_budget(run_with_budget);
+        // counts up forever without ever repeating a state
+        let instructions = parse_program(&["inc a", "jnz 1 -1"], 4).unwrap();
+        assert!(matches!(run_with_budget(&instructions, [0isize; 4], 10_000), RunOutcome::BudgetExceeded(_)));
+    }
+
+    #[test]
+    fn test_run_detects_loops() {
+        let instructions = parse_program(&["cpy 1 a", "jnz a 0"], 4).unwrap();
+        assert_eq!(RunOutcome::Looped([1, 0, 0, 0]), run_with_budget(&instructions, [0isize; 4], usize::MAX));
+        // b swings between 0 and 1 forever
+        let instructions = parse_program(&["inc b", "dec b", "jnz 1 -2"], 4).unwrap();
+        assert!(matches!(run_with_budget(&instructions, [0isize; 4], usize::MAX), RunOutcome::Looped(_)));
+    }
+
     #[test]
     fn test_part2() {
         let lines: Vec<&str> = TESTINPUT.lines().collect();
@@ -0,0 +1,34 @@
+// the implementations of this crate for the shared fuzzer, see assembunny::fuzz.
+
+use assembunny::fuzz::{Implementation, Outcome, Regs, STEP_LIMIT};
+use assembunny::{Instruction, InstructionSet};
+
+use crate::{execute, run_compiled};
+
+fn interpreter(program: &[Instruction], regs: Regs, checked: bool) -> Option<Outcome> {
+    let mut steps = 0;
+    let (regs, _) = execute(program, regs, checked, |_, _, _, _, _| {
+        steps += 1;
+        steps < STEP_LIMIT
+    }).ok()?;
+    Some(Outcome { regs, steps, output: Vec::new() })
+}
+
+fn unchecked_interpreter(program: &[Instruction], regs: Regs) -> Option<Outcome> {
+    interpreter(program, regs, false)
+}
+
+fn checked_interpreter(program: &[Instruction], regs: Regs) -> Option<Outcome> {
+    interpreter(program, regs, true)
+}
+
+fn compiled(program: &[Instruction], regs: Regs) -> Option<Outcome> {
+    let (regs, steps) = run_compiled(program, regs, STEP_LIMIT);
+    Some(Outcome { regs, steps, output: Vec::new() })
+}
+
+pub const IMPLEMENTATIONS: &[Implementation] = &[
+    Implementation { name: "day23 interpreter", instructions: InstructionSet::Toggle, run: unchecked_interpreter },
+    Implementation { name: "day23 checked interpreter", instructions: InstructionSet::Toggle, run: checked_interpreter },
+    Implementation { name: "day23 compiled", instructions: InstructionSet::Toggle, run: compiled }
+];
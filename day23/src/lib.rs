@@ -0,0 +1,346 @@
+#![allow(unused)]
+#![allow(dead_code)]
+
+// the interpreter and the compiled backend, a library so that other days can compare theirs with them
+
+use assembunny::{add, IValue, Instruction, InstructionSet, LoopDetector, Overflow, RunOutcome};
+
+pub mod fuzz;
+
+// the instructions of this day's puzzle
+pub const INSTRUCTIONS: InstructionSet = InstructionSet::Toggle;
+
+pub fn parse_program(lines: &[&str], num_registers: usize) -> Result<Vec<Instruction>, String> {
+    assembunny::parse_program(lines, num_registers, INSTRUCTIONS)
+}
+
+// observe is called after every executed instruction with its ip, the instruction,
+// the registers before and after it, and the index of the instruction it toggled.
+// Execution stops early once it returns false. Returns the registers and the next ip,
+// which is past the end of the program once it halted
+pub fn execute<const N: usize, F>(instructions: &[Instruction], regs: [isize; N], checked: bool, observe: F) -> Result<([isize; N], usize), Overflow>
+where F: FnMut(usize, Instruction, &[isize; N], &[isize; N], Option<usize>) -> bool {
+    execute_from(instructions, 0, regs, checked, observe)
+}
+
+// like execute, but starts at ip start instead of the first instruction
+pub fn execute_from<const N: usize, F>(instructions: &[Instruction], start: usize, regs: [isize; N], checked: bool, mut observe: F) -> Result<([isize; N], usize), Overflow>
+where F: FnMut(usize, Instruction, &[isize; N], &[isize; N], Option<usize>) -> bool {
+    let mut virtual_instructions = instructions.to_vec();
+    let mut regs = regs;
+    let mut ip = start;
+    let resolve = |val: &IValue, regs: &[isize; N]| -> isize {
+        match val {
+            IValue::Literal(intval) => *intval,
+            IValue::Register(index) => regs[*index as usize]
+        }
+    };
+    while (ip < virtual_instructions.len()) {
+        let current = ip;
+        let instruction = virtual_instructions[current];
+        let overflow = move || Overflow { ip: current, instruction };
+        let before = regs;
+        let mut toggled = None;
+        ip += 1;
+        match instruction {
+            Instruction::Cpy(src, dest) => {
+                if dest.is_register() {
+                    let srcval = resolve(&src, &regs);
+                    regs[dest.unwrap() as usize] = srcval;
+                }
+            },
+            Instruction::Inc(dest) => {
+                if dest.is_register() {
+                    let dest = dest.unwrap() as usize;
+                    regs[dest] = add(regs[dest], 1, checked).ok_or_else(overflow)?;
+                }
+            },
+            Instruction::Dec(dest) => {
+                if dest.is_register() {
+                    let dest = dest.unwrap() as usize;
+                    regs[dest] = add(regs[dest], -1, checked).ok_or_else(overflow)?;
+                }
+            },
+            Instruction::Jnz(src, jmprange) => {
+                let srcval = resolve(&src, &regs);
+                if srcval != 0 {
+                    ip = (add(ip as isize - 1, resolve(&jmprange, &regs), checked).ok_or_else(overflow)?) as usize;
+                }
+            },
+            Instruction::Tgl(dest) => {
+                let index = add(ip as isize - 1, resolve(&dest, &regs), checked).ok_or_else(overflow)?;
+                if index >= 0 && (index as usize) < virtual_instructions.len() {
+                    let index = index as usize;
+                    virtual_instructions[index] = virtual_instructions[index].toggle();
+                    toggled = Some(index);
+                }
+            },
+            Instruction::Out(_) => unreachable!("out is not part of day 23, parse_program rejects it")
+        }
+        if !observe(current, instruction, &before, &regs, toggled) {
+            break;
+        }
+    }
+    Ok((regs, ip))
+}
+
+pub fn run<const N: usize>(instructions: &[Instruction], regs: [isize; N]) -> [isize; N] {
+    execute(instructions, regs, false, |_, _, _, _, _| true).unwrap_or_else(|_| unreachable!("unchecked execution never traps")).0
+}
+
+// like run, but traps on overflow with the offending ip and (possibly toggled) instruction
+pub fn run_checked<const N: usize>(instructions: &[Instruction], regs: [isize; N]) -> Result<[isize; N], Overflow> {
+    execute(instructions, regs, true, |_, _, _, _, _| true).map(|(regs, _)| regs)
+}
+
+// like run, but gives up once the program needs more than budget steps
+// or provably runs forever
+pub fn run_with_budget<const N: usize>(instructions: &[Instruction], regs: [isize; N], budget: usize) -> RunOutcome<N> {
+    if budget == 0 {
+        return if instructions.is_empty() {RunOutcome::Halted(regs)} else {RunOutcome::BudgetExceeded(regs)};
+    }
+    let mut program = instructions.to_vec();
+    let mut steps = 0;
+    let mut detector = LoopDetector::new();
+    let mut looped = false;
+    // the executed ip, the registers and the program after it determine the rest of the run
+    let (regs, ip) = execute(instructions, regs, false, |ip, _, _, regs, toggled| {
+        if let Some(index) = toggled {
+            program[index] = program[index].toggle();
+        }
+        steps += 1;
+        looped = detector.looped(ip, regs, &program);
+        !looped && steps < budget
+    }).unwrap_or_else(|_| unreachable!("unchecked execution never traps"));
+    if looped {
+        RunOutcome::Looped(regs)
+    } else if ip < instructions.len() {
+        RunOutcome::BudgetExceeded(regs)
+    } else {
+        RunOutcome::Halted(regs)
+    }
+}
+
+// a compiled instruction takes the registers and its own ip, and returns the next ip
+pub type CompiledOp<const N: usize> = Box<dyn Fn(&mut [isize; N], usize) -> usize>;
+
+// loops of the puzzle inputs that the compiled code runs as a single operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FusedLoop {
+    // inc dest, dec counter, jnz counter -2 (or dec before inc): dest += counter
+    Add { dest: usize, counter: usize },
+    // cpy src inner, an add loop of inner into dest, dec counter, jnz counter -5:
+    // dest += src * counter
+    Multiply { src: IValue, inner: usize, dest: usize, counter: usize }
+}
+
+impl FusedLoop {
+    // the loop starting at ip, if there is one
+    fn find(instructions: &[Instruction], ip: usize) -> Option<FusedLoop> {
+        let add = |ip: usize| match instructions.get(ip..ip + 3)? {
+            [Instruction::Inc(IValue::Register(dest)), Instruction::Dec(IValue::Register(counter)), Instruction::Jnz(IValue::Register(cond), IValue::Literal(-2))]
+            | [Instruction::Dec(IValue::Register(counter)), Instruction::Inc(IValue::Register(dest)), Instruction::Jnz(IValue::Register(cond), IValue::Literal(-2))]
+                if cond == counter && dest != counter => Some((*dest as usize, *counter as usize)),
+            _ => None
+        };
+        let multiply = || {
+            let (Some(Instruction::Cpy(src, IValue::Register(inner))), Some((dest, add_counter)), Some([Instruction::Dec(IValue::Register(counter)), Instruction::Jnz(IValue::Register(cond), IValue::Literal(-5))]))
+                = (instructions.get(ip), add(ip + 1), instructions.get(ip + 4..ip + 6)) else {
+                return None;
+            };
+            let (src, inner, counter) = (*src, *inner as usize, *counter as usize);
+            // only the source may be a literal, and no register may be used for two things
+            let overlaps = match src {
+                IValue::Register(src) => [inner, dest, counter].contains(&(src as usize)),
+                IValue::Literal(_) => false
+            };
+            let distinct = inner == add_counter && *cond as usize == counter && counter != inner && counter != dest && !overlaps;
+            distinct.then_some(FusedLoop::Multiply { src, inner, dest, counter })
+        };
+        multiply().or_else(|| add(ip).map(|(dest, counter)| FusedLoop::Add { dest, counter }))
+    }
+}
+
+pub struct CompiledProgram<const N: usize> {
+    // the program as modified by tgl so far
+    program: Vec<Instruction>,
+    ops: Vec<CompiledOp<N>>,
+    // the fused loop starting at every ip
+    fused: Vec<Option<FusedLoop>>
+}
+
+// translates an instruction into a closure with its operands already resolved,
+// so the hot loop neither matches on Instruction nor on IValue
+fn compile_op<const N: usize>(instruction: Instruction, at: usize, len: usize) -> CompiledOp<N> {
+    // jumping outside of the program halts it, just like in the interpreter
+    let jump_target = move |ip: usize, offset: isize| -> usize {
+        let target = ip as isize + offset;
+        if target < 0 || target as usize >= len {len} else {target as usize}
+    };
+    match instruction {
+        Instruction::Cpy(IValue::Literal(value), IValue::Register(dest)) => {
+            let dest = dest as usize;
+            Box::new(move |regs, ip| {regs[dest] = value; ip + 1})
+        },
+        Instruction::Cpy(IValue::Register(src), IValue::Register(dest)) => {
+            let (src, dest) = (src as usize, dest as usize);
+            Box::new(move |regs, ip| {regs[dest] = regs[src]; ip + 1})
+        },
+        Instruction::Inc(IValue::Register(dest)) => {
+            let dest = dest as usize;
+            Box::new(move |regs, ip| {regs[dest] += 1; ip + 1})
+        },
+        Instruction::Dec(IValue::Register(dest)) => {
+            let dest = dest as usize;
+            Box::new(move |regs, ip| {regs[dest] -= 1; ip + 1})
+        },
+        Instruction::Jnz(IValue::Literal(cond), IValue::Literal(offset)) => {
+            if cond == 0 {
+                Box::new(|_, ip| ip + 1)
+            } else {
+                let target = jump_target(at, offset);
+                Box::new(move |_, _| target)
+            }
+        },
+        Instruction::Jnz(IValue::Register(cond), IValue::Literal(offset)) => {
+            let cond = cond as usize;
+            let target = jump_target(at, offset);
+            Box::new(move |regs, ip| if regs[cond] != 0 {target} else {ip + 1})
+        },
+        Instruction::Jnz(cond, IValue::Register(offset)) => {
+            let offset = offset as usize;
+            Box::new(move |regs, ip| {
+                let condval = match cond {
+                    IValue::Literal(intval) => intval,
+                    IValue::Register(index) => regs[index as usize]
+                };
+                if condval != 0 {jump_target(ip, regs[offset])} else {ip + 1}
+            })
+        },
+        // past the end of the program, but never len like jumps out of it
+        Instruction::Tgl(_) => Box::new(move |_, ip| len + 1 + ip),
+        // invalid instructions (e.g. writing to a literal) are skipped
+        _ => Box::new(|_, ip| ip + 1)
+    }
+}
+
+impl<const N: usize> CompiledProgram<N> {
+    // compiles every instruction and finds the fused loops.
+    // A tgl changes the program, so the compiled code stops there, see toggle_at and toggle
+    pub fn compile(instructions: &[Instruction]) -> CompiledProgram<N> {
+        let len = instructions.len();
+        let ops = instructions.iter().enumerate().map(|(at, instruction)| compile_op(*instruction, at, len)).collect();
+        let fused = (0..len).map(|ip| FusedLoop::find(instructions, ip)).collect();
+        CompiledProgram { program: instructions.to_vec(), ops, fused }
+    }
+
+    pub fn program(&self) -> &[Instruction] {
+        &self.program
+    }
+
+    // the ip of the tgl that returned next_ip instead of executing, if it was one
+    pub fn toggle_at(&self, next_ip: usize) -> Option<usize> {
+        next_ip.checked_sub(self.ops.len() + 1)
+    }
+
+    // toggles the instruction at index and recompiles only what depends on it:
+    // the instruction itself and the fused loops it may be part of
+    pub fn toggle(&mut self, index: usize) {
+        self.program[index] = self.program[index].toggle();
+        self.ops[index] = compile_op(self.program[index], index, self.program.len());
+        // a multiply loop is the longest, with six instructions
+        for ip in index.saturating_sub(5)..=index {
+            self.fused[ip] = FusedLoop::find(&self.program, ip);
+        }
+    }
+
+    // runs the fused loop at ip in one go if it has a positive counter (otherwise it would
+    // wrap around) and it fits into max_steps. Returns the next ip and the steps it replaced
+    fn run_fused(&self, ip: usize, regs: &mut [isize; N], max_steps: usize) -> Option<(usize, usize)> {
+        match self.fused[ip]? {
+            FusedLoop::Add { dest, counter } => {
+                let count = regs[counter];
+                let steps = (count > 0).then(|| (count as usize).checked_mul(3))??;
+                if steps > max_steps {
+                    return None;
+                }
+                regs[dest] += count;
+                regs[counter] = 0;
+                Some((ip + 3, steps))
+            },
+            FusedLoop::Multiply { src, inner, dest, counter } => {
+                let value = match src {
+                    IValue::Literal(intval) => intval,
+                    IValue::Register(index) => regs[index as usize]
+                };
+                let count = regs[counter];
+                // every round copies, runs the add loop, decrements and jumps
+                let steps = (count > 0 && value > 0).then(|| (value as usize).checked_mul(3)?.checked_add(3)?.checked_mul(count as usize))??;
+                if steps > max_steps {
+                    return None;
+                }
+                regs[dest] += value * count;
+                regs[inner] = 0;
+                regs[counter] = 0;
+                Some((ip + 6, steps))
+            }
+        }
+    }
+
+    // runs from ip for at most max_steps steps, until the program halts or reaches a tgl.
+    // Returns the registers, the next ip (see toggle_at) and the number of steps taken,
+    // which leaves out the tgl and counts every instruction a fused loop replaced
+    pub fn run(&self, ip: usize, regs: [isize; N], max_steps: usize) -> ([isize; N], usize, usize) {
+        let mut regs = regs;
+        let mut ip = ip;
+        let mut steps = 0;
+        while ip < self.ops.len() && steps < max_steps {
+            if let Some((next_ip, fused_steps)) = self.run_fused(ip, &mut regs, max_steps - steps) {
+                ip = next_ip;
+                steps += fused_steps;
+                continue;
+            }
+            let next_ip = self.ops[ip](&mut regs, ip);
+            if self.toggle_at(next_ip).is_some() {
+                return (regs, next_ip, steps);
+            }
+            ip = next_ip;
+            steps += 1;
+        }
+        (regs, ip, steps)
+    }
+}
+
+// runs the compiled code for at most max_steps steps and executes only the tgl instructions
+// with the interpreter, recompiling just the instruction they toggled.
+// Returns the registers and the number of steps taken
+pub fn run_compiled<const N: usize>(instructions: &[Instruction], regs: [isize; N], max_steps: usize) -> ([isize; N], usize) {
+    let mut compiled = CompiledProgram::compile(instructions);
+    let mut regs = regs;
+    let mut ip = 0;
+    let mut steps = 0;
+    while ip < instructions.len() && steps < max_steps {
+        let (after, next_ip, taken) = compiled.run(ip, regs, max_steps - steps);
+        (regs, ip) = (after, next_ip);
+        steps += taken;
+        let Some(toggle) = compiled.toggle_at(ip) else {
+            continue;
+        };
+        let mut toggled = None;
+        (regs, ip) = execute_from(compiled.program(), toggle, regs, false, |_, _, _, _, index| {
+            toggled = index;
+            false
+        }).unwrap_or_else(|_| unreachable!("unchecked execution never traps"));
+        steps += 1;
+        if let Some(index) = toggled {
+            compiled.toggle(index);
+        }
+    }
+    (regs, steps)
+}
+
+// like run, but with compiled code, which runs the multiplications of the puzzle
+// in one go (see FusedLoop and bench_compiled_vs_interpreter_with_tgl)
+pub fn run_fast<const N: usize>(instructions: &[Instruction], regs: [isize; N]) -> [isize; N] {
+    run_compiled(instructions, regs, usize::MAX).0
+}
@@ -1,134 +1,16 @@
 #![allow(unused)]
 #![allow(dead_code)]
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum IValue {
-    Register(u8),
-    Literal(isize)
-}
+use assembunny::{IValue, Instruction, Overflow, RunOutcome, NUM_REGISTERS};
+use day23::*;
 
-impl IValue {
-    fn unwrap(&self) -> isize {
-        match self {
-            IValue::Literal(intval) => *intval as isize,
-            IValue::Register(index) => *index as isize
-        }
-    }
-
-    fn is_literal(&self) -> bool {
-        matches!(self, IValue::Literal(_))
-    }
-
-    fn is_register(&self) -> bool {
-        matches!(self, IValue::Register(_))
-    }
-}
-
-#[derive(Debug, Clone)]
-enum Instruction {
-    Cpy(IValue, IValue),
-    Inc(IValue),
-    Dec(IValue),
-    Jnz(IValue, IValue),
-    Tgl(IValue)
-}
-
-impl From<&str> for Instruction {
-    fn from(line: &str) -> Self {
-        let (name, args) = line.split_once(' ').unwrap();
-        let arg_to_value = |s: &str| -> IValue {
-            if s.chars().all(|c| c == '-' || c.is_numeric()) {
-                IValue::Literal(s.parse().unwrap())
-            } else {
-                assert!(s.len() == 1);
-                IValue::Register(s.bytes().next().unwrap() as u8 - 'a' as u8)
-            }
-        };
-        let args = args.split_whitespace().map(arg_to_value).collect::<Vec<_>>();
-        match name.to_lowercase().as_str() {
-            "cpy" => Instruction::Cpy(args[0], args[1]),
-            "inc" => Instruction::Inc(args[0]),
-            "dec" => Instruction::Dec(args[0]),
-            "jnz" => Instruction::Jnz(args[0], args[1]),
-            "tgl" => Instruction::Tgl(args[0]),
-            _ => panic!("Unknown instruction")
-        }
-    }
-}
-
-impl Instruction {
-    fn toggle(&self) -> Instruction {
-        match *self {
-            Instruction::Inc(val) => Instruction::Dec(val),
-            Instruction::Dec(val) | Instruction::Tgl(val) => Instruction::Inc(val),
-            Instruction::Cpy(val1, val2) => Instruction::Jnz(val1, val2),
-            Instruction::Jnz(val1, val2) => Instruction::Cpy(val1, val2)
-        }
-    }
-}
-
-fn run(instructions: &[Instruction], regs: [isize; 4]) -> [isize; 4] {
-    let mut virtual_instructions = instructions.to_vec();
-    let mut toggled = vec![false; virtual_instructions.len()];
-    let mut regs = regs;
-    let mut ip = 0;
-    let resolve = |val: &IValue, regs: &[isize; 4]| -> isize {
-        match val {
-            IValue::Literal(intval) => *intval,
-            IValue::Register(index) => regs[*index as usize]
-        }
-    };
-    while (ip < virtual_instructions.len()) {
-        let instruction = &virtual_instructions[ip];
-        ip += 1;
-        match instruction {
-            Instruction::Cpy(src, dest) => {
-                if dest.is_register() {
-                    let srcval = resolve(src, &regs);
-                    regs[dest.unwrap() as usize] = srcval;
-                }
-            },
-            Instruction::Inc(dest) => {
-                if dest.is_register() {
-                    regs[dest.unwrap() as usize] += 1;
-                }
-            },
-            Instruction::Dec(dest) => {
-                if dest.is_register() {
-                    regs[dest.unwrap() as usize] -= 1;
-                }
-            },
-            Instruction::Jnz(src, jmprange) => {
-                let srcval = resolve(src, &regs);
-                if srcval != 0 {
-                    ip = (ip as isize + resolve(jmprange, &regs) - 1) as usize;
-                }
-            },
-            Instruction::Tgl(dest) => {
-                let index = ip as isize + resolve(dest, &regs) - 1;
-                if index >= 0 && (index as usize) < virtual_instructions.len() {
-                    let index = index as usize;
-                    // TODO: It is unclear whether we want to actuallt toggle likt this,
-                    // or just reapply the toggling rule as if it weren't toggled already
-                    if toggled[index] {
-                        virtual_instructions[index] = instructions[index].clone();
-                        toggled[index] = false;
-                    } else {
-                        virtual_instructions[index as usize] = virtual_instructions[index as usize].toggle();
-                        toggled[index] = true;
-                    }
-                }
-            }
-        }
-    }
-    regs
-}
+mod trace;
 
 fn part1(lines: &Vec<&str>) -> Option<usize> {
-    let instructions = lines.iter().map(|l| Instruction::from(*l)).collect::<Vec<_>>();
+    let instructions = parse_program(lines, NUM_REGISTERS).unwrap_or_else(|err| panic!("{}", err));
     let mut regs = [0isize; 4];
     regs[0] = 7;
-    let regs = run(&instructions, regs);
+    let regs = run_fast(&instructions, regs);
     Some(regs[0] as usize)
 }
 
@@ -146,20 +28,75 @@ fn part2(lines: &Vec<&str>) -> Option<usize> {
     // my puzzle input calculates this, it will vary for yours
     println!("No general solution for part 2 here - but just replace 98 and 86 with the literals in your puzzle input.");
     return Some(fac(12) as usize + 98 * 86);
-    let instructions = lines.iter().map(|l| Instruction::from(*l)).collect::<Vec<_>>();
+    let instructions = parse_program(lines, NUM_REGISTERS).unwrap_or_else(|err| panic!("{}", err));
     let mut regs = [0isize; 4];
     regs[0] = 12;
     let regs = run(&instructions, regs);
     Some(regs[0] as usize)
 }
 
+fn trace_main(args: &[String]) {
+    use trace::Trace;
+    let arg = |index: usize| -> &str {
+        args.get(index).map(|a| a.as_str()).unwrap_or_else(|| {
+            println!("Missing argument, run without arguments for usage");
+            std::process::exit(1);
+        })
+    };
+    let load = |path: &str| Trace::load(path).unwrap_or_else(|err| {
+        println!("Could not load trace {}: {}", path, err);
+        std::process::exit(1);
+    });
+    match args[1].as_str() {
+        "--trace" => {
+            let contents = std::fs::read_to_string(arg(3)).expect("Could not read in file");
+            let lines: Vec<&str> = contents.lines().collect();
+            let instructions = parse_program(&lines, NUM_REGISTERS).unwrap_or_else(|err| {
+                println!("{}", err);
+                std::process::exit(1);
+            });
+            let mut regs = [0isize; NUM_REGISTERS];
+            regs[0] = args.get(4).map(|a| a.parse().expect("Register a must be a number")).unwrap_or(7);
+            let (trace, regs) = Trace::record(&instructions, regs);
+            trace.save(arg(2)).expect("Could not write trace");
+            println!("Recorded {} steps, final registers {:?}", trace.steps.len(), regs);
+        },
+        "--replay" => trace::replay_interactive(&load(arg(2))),
+        "--diff" => {
+            let (left, right) = (load(arg(2)), load(arg(3)));
+            if left.initial_regs != right.initial_regs {
+                println!("Initial registers differ: {:?} vs {:?}", left.initial_regs, right.initial_regs);
+            }
+            match trace::diff(&left, &right) {
+                None => println!("Executed steps are identical"),
+                Some(divergence) => {
+                    println!("Traces diverge at step {}", divergence.step);
+                    println!("< {:?}", divergence.left);
+                    println!("> {:?}", divergence.right);
+                }
+            }
+        },
+        flag => {
+            println!("Unknown option {}", flag);
+            std::process::exit(1);
+        }
+    }
+}
+
 fn main() {
     use std::fs;
     use std::env;
     use std::time::Instant;
     let args: Vec<String> =  env::args().collect();
+    if args.len() > 1 && args[1].starts_with("--") {
+        trace_main(&args);
+        return;
+    }
     let infile = args.get(1).unwrap_or_else(|| {
         println!("Usage: {} <puzzle input>", args[0]);
+        println!("       {} --trace <trace file> <puzzle input> [register a]", args[0]);
+        println!("       {} --replay <trace file>", args[0]);
+        println!("       {} --diff <trace file> <trace file>", args[0]);
         std::process::exit(1);
     });
 
@@ -194,12 +131,346 @@ dec a";
 
     #[test]
     fn test_part1() {
-        let lines: Vec<&str> = TESTINPUT.lines().collect();
-        let instructions = lines.iter().map(|l| Instruction::from(*l)).collect::<Vec<_>>();
+        let instructions = parse(TESTINPUT);
         let regs = run(&instructions, [0isize; 4]);
         assert_eq!(3, regs[0]);
     }
 
+    static LOOPINPUT: &str = "cpy 41 a
+inc a
+inc a
+dec a
+jnz a 2
+dec a
+cpy 300 b
+cpy b c
+inc a
+dec c
+jnz c -2
+dec b
+jnz b -5";
+
+    // the shape of a puzzle input: a factorial with multiplications by repeated addition,
+    // the tgl turning the end of the program into an addition of 98 * 86
+    static BUNNYINPUT: &str = "cpy a b
+dec b
+cpy a d
+cpy 0 a
+cpy b c
+inc a
+dec c
+jnz c -2
+dec d
+jnz d -5
+dec b
+cpy b c
+cpy c d
+dec d
+inc c
+jnz d -2
+tgl c
+cpy -16 c
+jnz 1 c
+cpy 98 c
+jnz 86 d
+inc a
+inc d
+jnz d -2
+inc c
+jnz c -5";
+
+    fn parse(input: &str) -> Vec<Instruction> {
+        parse_program(&input.lines().collect::<Vec<_>>(), NUM_REGISTERS).unwrap()
+    }
+
+    // the registers and number of steps after at most max_steps steps of the interpreter
+    fn interpret(instructions: &[Instruction], regs: [isize; 4], max_steps: usize) -> ([isize; 4], usize) {
+        if max_steps == 0 {
+            return (regs, 0);
+        }
+        let mut steps = 0;
+        let (regs, _) = execute(instructions, regs, false, |_, _, _, _, _| {
+            steps += 1;
+            steps < max_steps
+        }).unwrap();
+        (regs, steps)
+    }
+
+    #[test]
+    fn test_compiled_matches_interpreter() {
+        let instructions = parse(LOOPINPUT);
+        let program = CompiledProgram::compile(&instructions);
+        for regs in [[0isize; 4], [1, 2, 3, 4], [0, 0, 1, 0]] {
+            let (interpreted, steps) = interpret(&instructions, regs, usize::MAX);
+            assert_eq!((interpreted, instructions.len(), steps), program.run(0, regs, usize::MAX));
+        }
+        assert_eq!(42 + 300 * 301 / 2, program.run(0, [0isize; 4], usize::MAX).0[0]);
+    }
+
+    #[test]
+    fn test_compiled_jumps_out_of_program() {
+        let instructions = parse("cpy 3 b\njnz 1 b\ninc a\njnz 1 -9\ninc a");
+        let program = CompiledProgram::compile(&instructions);
+        assert_eq!((run(&instructions, [0isize; 4]), instructions.len(), 3), program.run(0, [0isize; 4], usize::MAX));
+    }
+
+    #[test]
+    fn test_compiled_stops_at_tgl() {
+        let instructions = parse(TESTINPUT);
+        let program = CompiledProgram::compile(&instructions);
+        let (regs, ip, steps) = program.run(0, [0isize; 4], usize::MAX);
+        assert_eq!(([2, 0, 0, 0], Some(1), 1), (regs, program.toggle_at(ip), steps));
+        assert_eq!(3, run_fast(&instructions, [0isize; 4])[0]);
+        let instructions = parse(BUNNYINPUT);
+        for a in 6..=8 {
+            assert_eq!(run(&instructions, [a, 0, 0, 0]), run_fast(&instructions, [a, 0, 0, 0]));
+        }
+        assert_eq!(fac(7) as isize + 98 * 86, run_fast(&instructions, [7, 0, 0, 0])[0]);
+    }
+
+    #[test]
+    fn test_compiled_fused_loops() {
+        let instructions = parse("cpy 5 c\ninc a\ndec c\njnz c -2\ndec b\ninc d\njnz b -2");
+        let mut program = CompiledProgram::<4>::compile(&instructions);
+        // the loop adds c to a in one go, but still counts every step it replaced
+        assert_eq!(([5, 0, 0, 0], 4, 16), program.run(0, [0isize; 4], 16));
+        // not enough steps left for the whole loop, so it is stepped through
+        assert_eq!(([2, 0, 3, 0], 1, 7), program.run(0, [0isize; 4], 7));
+        // dec before inc, and a counter that would have to wrap around is not added up
+        for regs in [[0, 3, 0, 0], [0, -2, 0, 0]] {
+            for max_steps in [0, 5, 100] {
+                assert_eq!(interpret(&instructions, regs, max_steps), run_compiled(&instructions, regs, max_steps));
+            }
+        }
+        // toggling the dec in place turns the loop into one that never ends
+        program.toggle(2);
+        assert_eq!(Instruction::Inc(IValue::Register(2)), program.program()[2]);
+        let mut toggled = instructions.clone();
+        toggled[2] = toggled[2].toggle();
+        assert_eq!(interpret(&toggled, [0isize; 4], 50), {
+            let (regs, _, steps) = program.run(0, [0isize; 4], 50);
+            (regs, steps)
+        });
+        program.toggle(2);
+        assert_eq!(([5, 0, 0, 0], 4, 16), program.run(0, [0isize; 4], 16));
+        // a += b * d, every round takes 3 * b + 3 steps
+        let instructions = parse("cpy b c\ninc a\ndec c\njnz c -2\ndec d\njnz d -5");
+        let program = CompiledProgram::<4>::compile(&instructions);
+        assert_eq!(([13, 3, 0, 0], 6, 48), program.run(0, [1, 3, 0, 4], 48));
+        for regs in [[1, 3, 0, 4], [0, 0, 0, 4], [0, -1, 0, 2], [0, 2, 0, -1]] {
+            for max_steps in [0, 20, 47, 48, 1000] {
+                assert_eq!(interpret(&instructions, regs, max_steps), run_compiled(&instructions, regs, max_steps), "{:?} {}", regs, max_steps);
+            }
+        }
+        // multiplying by a literal, and a source that is also the counter, which is no multiplication
+        for source in ["cpy 7 c\ninc a\ndec c\njnz c -2\ndec d\njnz d -5", "cpy d c\ninc a\ndec c\njnz c -2\ndec d\njnz d -5"] {
+            let instructions = parse(source);
+            assert_eq!(interpret(&instructions, [0, 0, 0, 5], 1000), run_compiled(&instructions, [0isize, 0, 0, 5], 1000), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_run_compiled_counts_steps() {
+        let instructions = parse(BUNNYINPUT);
+        for max_steps in [0, 1, 10, 100, 1000, 10_000, usize::MAX] {
+            assert_eq!(interpret(&instructions, [6, 0, 0, 0], max_steps), run_compiled(&instructions, [6isize, 0, 0, 0], max_steps));
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_compiled_vs_interpreter() {
+        use std::time::Instant;
+        let instructions = parse(&LOOPINPUT.replace("300", "3000"));
+        let start = Instant::now();
+        let interpreted = run(&instructions, [0isize; 4]);
+        let interpreter_time = start.elapsed();
+        let start = Instant::now();
+        let compiled = run_fast(&instructions, [0isize; 4]);
+        let compiled_time = start.elapsed();
+        assert_eq!(interpreted, compiled);
+        println!("interpreter: {:?}, compiled: {:?}", interpreter_time, compiled_time);
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_compiled_vs_interpreter_with_tgl() {
+        use std::time::Instant;
+        let instructions = parse(BUNNYINPUT);
+        for a in [7, 10, 11] {
+            let start = Instant::now();
+            let interpreted = run(&instructions, [a, 0, 0, 0]);
+            let interpreter_time = start.elapsed();
+            let start = Instant::now();
+            let compiled = run_fast(&instructions, [a, 0, 0, 0]);
+            let compiled_time = start.elapsed();
+            assert_eq!(interpreted, compiled);
+            println!("a = {}: interpreter: {:?}, compiled: {:?}", a, interpreter_time, compiled_time);
+        }
+    }
+
+    #[test]
+    fn test_toggle_twice_reapplies_rule() {
+        // the tgl at index 2 is toggled to inc a, then to dec a (not back to tgl a)
+        let instructions = parse("tgl 2\ntgl 1\ntgl a");
+        assert_eq!(-1, run(&instructions, [0isize; 4])[0]);
+    }
+
+    #[test]
+    fn test_toggled_invalid_instruction_is_skipped() {
+        // jnz 1 2 becomes cpy 1 2, which must be skipped instead of jumping over inc a
+        let instructions = parse("tgl 1\njnz 1 2\ninc a");
+        assert_eq!(1, run(&instructions, [0isize; 4])[0]);
+    }
+
+    #[test]
+    fn test_toggle_outside_program_is_ignored() {
+        let instructions = parse("tgl 5\ntgl -3\ninc a");
+        assert_eq!(1, run(&instructions, [0isize; 4])[0]);
+    }
+
+    #[test]
+    fn test_toggle_self() {
+        // tgl a with a == 0 toggles itself into inc a, which is not executed anymore
+        let instructions = parse("tgl a\njnz a 2\ninc b");
+        assert_eq!([0, 1, 0, 0], run(&instructions, [0isize; 4]));
+    }
+
+    #[test]
+    fn test_run_with_budget() {
+        assembunny::fuzz::check_budget(run_with_budget);
+        let instructions = parse(LOOPINPUT);
+        assert_eq!(RunOutcome::Halted(run(&instructions, [7, 0, 0, 0])), run_with_budget(&instructions, [7isize, 0, 0, 0], usize::MAX));
+    }
+
+    #[test]
+    fn test_run_detects_loops() {
+        assert!(matches!(run_with_budget(&parse("jnz 1 0"), [0isize; 4], usize::MAX), RunOutcome::Looped(_)));
+        // inc a is toggled into dec a and back, a returns to 0 every second round
+        let instructions = parse("tgl 1\ninc a\njnz 1 -2");
+        assert_eq!(RunOutcome::Looped([0, 0, 0, 0]), run_with_budget(&instructions, [0isize; 4], usize::MAX));
+        // never repeats, b keeps growing
+        let instructions = parse("tgl 1\ninc a\ninc b\njnz 1 -3");
+        assert!(matches!(run_with_budget(&instructions, [0isize; 4], 10_000), RunOutcome::BudgetExceeded(_)));
+    }
+
+    #[test]
+    fn test_parse_toggle_instructions() {
+        assert_eq!(Ok(Instruction::Tgl(IValue::Register(3))), Instruction::parse("tgl d", 4, INSTRUCTIONS));
+        // tgl may turn these into valid instructions
+        assert_eq!(Ok(Instruction::Cpy(IValue::Literal(1), IValue::Literal(2))), Instruction::parse("cpy 1 2", 4, INSTRUCTIONS));
+        assert!(Instruction::parse("inc 1", 4, INSTRUCTIONS).is_ok());
+        assert_eq!("line 2: unknown instruction 'out'", parse_program(&["tgl a", "out a"], 4).unwrap_err());
+    }
+
+    #[test]
+    fn test_more_registers() {
+        let instructions = parse_program(&["cpy 5 e", "inc a", "dec e", "jnz e -2"], 5).unwrap();
+        assert_eq!([5, 0, 0, 0, 0], run(&instructions, [0isize; 5]));
+        assert_eq!([5, 0, 0, 0, 0], run_fast(&instructions, [0isize; 5]));
+    }
+
+    #[test]
+    fn test_checked_overflow() {
+        // the trap reports the toggled instruction that was actually executed
+        let instructions = parse("tgl 1\ndec a");
+        let overflow = run_checked(&instructions, [isize::MAX, 0, 0, 0]).unwrap_err();
+        assert_eq!(Overflow { ip: 1, instruction: Instruction::Inc(IValue::Register(0)) }, overflow);
+        assert_eq!(Ok([isize::MAX - 1, 0, 0, 0]), run_checked(&instructions[1..], [isize::MAX, 0, 0, 0]));
+        let instructions = parse("cpy 1 b\njnz 1 a");
+        assert!(run_checked(&instructions, [isize::MAX, 0, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_trace_roundtrip() {
+        let instructions = parse(TESTINPUT);
+        let (trace, regs) = trace::Trace::record(&instructions, [0isize; 4]);
+        assert_eq!(run(&instructions, [0isize; 4]), regs);
+        let mut buffer = Vec::new();
+        trace.write_to(&mut buffer).unwrap();
+        assert_eq!(trace, trace::Trace::read_from(&mut buffer.as_slice()).unwrap());
+        assert!(trace::Trace::read_from(&mut &buffer[..buffer.len() - 1]).is_err());
+        assert!(trace::Trace::read_from(&mut &b"nope"[..]).is_err());
+    }
+
+    #[test]
+    fn test_trace_rejects_corrupt_data() {
+        let instructions = parse(TESTINPUT);
+        let (trace, _) = trace::Trace::record(&instructions, [0isize; 4]);
+        let read_back = |trace: &trace::Trace| {
+            let mut buffer = Vec::new();
+            trace.write_to(&mut buffer).unwrap();
+            trace::Trace::read_from(&mut buffer.as_slice()).map_err(|err| err.kind())
+        };
+        let mut corrupt = trace.clone();
+        corrupt.steps[2].toggled = Some(instructions.len());
+        assert_eq!(Err(std::io::ErrorKind::InvalidData), read_back(&corrupt));
+        let mut corrupt = trace.clone();
+        corrupt.steps[1].ip = instructions.len() + 3;
+        assert_eq!(Err(std::io::ErrorKind::InvalidData), read_back(&corrupt));
+        let mut corrupt = trace.clone();
+        corrupt.program[1] = Instruction::Cpy(IValue::Literal(1), IValue::Register(200));
+        assert_eq!(Err(std::io::ErrorKind::InvalidData), read_back(&corrupt));
+        // a program cut short, with steps still pointing past its end
+        let mut corrupt = trace.clone();
+        corrupt.program.truncate(2);
+        assert_eq!(Err(std::io::ErrorKind::InvalidData), read_back(&corrupt));
+        assert_eq!(trace, read_back(&trace).unwrap());
+    }
+
+    #[test]
+    fn test_trace_rejects_values_too_large_to_write() {
+        let (trace, _) = trace::Trace::record(&parse(TESTINPUT), [0isize; 4]);
+        let write = |trace: &trace::Trace| trace.write_to(&mut Vec::new()).map_err(|err| err.kind());
+        let mut large = trace.clone();
+        large.steps[1].ip = u32::MAX as usize + 1;
+        assert_eq!(Err(std::io::ErrorKind::InvalidInput), write(&large));
+        let mut large = trace.clone();
+        large.steps[2].toggled = Some(u32::MAX as usize + 1);
+        assert_eq!(Err(std::io::ErrorKind::InvalidInput), write(&large));
+        let mut large = trace.clone();
+        large.initial_regs = vec![0; 256];
+        assert_eq!(Err(std::io::ErrorKind::InvalidInput), write(&large));
+        assert_eq!(Ok(()), write(&trace));
+    }
+
+    #[test]
+    fn test_trace_replay() {
+        let instructions = parse(TESTINPUT);
+        let (trace, regs) = trace::Trace::record(&instructions, [0isize; 4]);
+        let mut replay = trace::Replay::new(&trace);
+        replay.seek(trace.steps.len());
+        assert_eq!(&regs[..], replay.regs());
+        // the toggles turned tgl a into inc a and cpy 1 a into jnz 1 a
+        assert_eq!(Instruction::Inc(IValue::Register(0)), replay.program()[3]);
+        assert_eq!(Instruction::Jnz(IValue::Literal(1), IValue::Register(0)), replay.program()[4]);
+        replay.seek(0);
+        assert_eq!(&[0isize; 4][..], replay.regs());
+        assert_eq!(&instructions[..], replay.program());
+        assert!(replay.backward().is_none());
+    }
+
+    #[test]
+    fn test_trace_diff() {
+        let instructions = parse(LOOPINPUT);
+        let (left, _) = trace::Trace::record(&instructions, [0isize; 4]);
+        assert_eq!(None, trace::diff(&left, &left.clone()));
+        // different initial registers show up in the first step's registers
+        let (right, _) = trace::Trace::record(&instructions, [0, 1, 0, 0]);
+        let divergence = trace::diff(&left, &right).unwrap();
+        assert_eq!(0, divergence.step);
+        assert_eq!(Some((0, instructions[0], vec![41, 1, 0, 0])), divergence.right);
+        // jnz a 2 jumps out of the truncated program, so it halts after five steps
+        let (right, _) = trace::Trace::record(&instructions[..6], [0isize; 4]);
+        assert_eq!(5, trace::diff(&left, &right).unwrap().step);
+        assert_eq!(None, trace::diff(&left, &right).unwrap().right);
+    }
+
+    #[test]
+    fn test_fuzz_implementations_agree() {
+        assembunny::fuzz::check(fuzz::IMPLEMENTATIONS, 23, 3000);
+    }
+
     #[test]
     #[ignore]
     fn test_part2() {
@@ -0,0 +1,382 @@
+// recording, replaying and diffing execution traces of assembunny programs.
+//
+// A trace stores the program and the initial registers once, followed by one record per
+// executed instruction: its ip, the registers it changed and the index of the instruction
+// it toggled. Everything else, like the instruction as executed (i.e. after toggling),
+// can be reconstructed by replaying, so traces stay small even for long runs.
+
+use crate::{execute, IValue, Instruction};
+use std::io::{self, BufRead, Read, Write};
+
+const MAGIC: &[u8; 4] = b"ABTR";
+const VERSION: u8 = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    pub ip: usize,
+    // (register, new value - old value)
+    pub deltas: Vec<(u8, isize)>,
+    pub toggled: Option<usize>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trace {
+    pub program: Vec<Instruction>,
+    pub initial_regs: Vec<isize>,
+    pub steps: Vec<Step>
+}
+
+impl Trace {
+    // runs the program like run() does, recording every executed instruction
+    pub fn record<const N: usize>(instructions: &[Instruction], regs: [isize; N]) -> (Trace, [isize; N]) {
+        let mut steps = Vec::new();
+        let observe = |ip: usize, _, before: &[isize; N], after: &[isize; N], toggled: Option<usize>| {
+            let deltas = before.iter()
+                .zip(after.iter())
+                .enumerate()
+                .filter(|(_, (old, new))| old != new)
+                .map(|(reg, (old, new))| (reg as u8, new.wrapping_sub(*old)))
+                .collect();
+            steps.push(Step { ip, deltas, toggled });
+            true
+        };
+        let (result, _) = execute(instructions, regs, false, observe)
+            .unwrap_or_else(|_| unreachable!("unchecked execution never traps"));
+        let trace = Trace { program: instructions.to_vec(), initial_regs: regs.to_vec(), steps };
+        (trace, result)
+    }
+
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION, fits(self.initial_regs.len(), "register count")?])?;
+        for reg in self.initial_regs.iter() {
+            write_int(writer, *reg)?;
+        }
+        writer.write_all(&fits::<u32>(self.program.len(), "program length")?.to_le_bytes())?;
+        for instruction in self.program.iter() {
+            write_instruction(writer, instruction)?;
+        }
+        writer.write_all(&(self.steps.len() as u64).to_le_bytes())?;
+        for step in self.steps.iter() {
+            writer.write_all(&fits::<u32>(step.ip, "ip")?.to_le_bytes())?;
+            writer.write_all(&[fits(step.deltas.len(), "number of changed registers")?])?;
+            for (reg, delta) in step.deltas.iter() {
+                writer.write_all(&[*reg])?;
+                write_int(writer, *delta)?;
+            }
+            match step.toggled {
+                Some(index) => {
+                    writer.write_all(&[1])?;
+                    writer.write_all(&fits::<u32>(index, "toggled instruction")?.to_le_bytes())?;
+                },
+                None => writer.write_all(&[0])?
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read_from<R: Read>(reader: &mut R) -> io::Result<Trace> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not an assembunny trace"));
+        }
+        let version = read_u8(reader)?;
+        if version != VERSION {
+            return Err(invalid_data(&format!("unsupported trace version {}", version)));
+        }
+        let num_registers = read_u8(reader)? as usize;
+        let initial_regs = (0..num_registers).map(|_| read_int(reader)).collect::<io::Result<Vec<_>>>()?;
+        let program_len = read_u32(reader)? as usize;
+        let program = (0..program_len).map(|_| read_instruction(reader)).collect::<io::Result<Vec<_>>>()?;
+        for instruction in program.iter() {
+            check_registers(instruction, num_registers)?;
+        }
+        let num_steps = read_u64(reader)? as usize;
+        let mut steps = Vec::new();
+        for _ in 0..num_steps {
+            let ip = read_u32(reader)? as usize;
+            if ip >= program_len {
+                return Err(invalid_data(&format!("ip {} out of range", ip)));
+            }
+            let num_deltas = read_u8(reader)? as usize;
+            let mut deltas = Vec::with_capacity(num_deltas);
+            for _ in 0..num_deltas {
+                let reg = read_u8(reader)?;
+                if reg as usize >= num_registers {
+                    return Err(invalid_data(&format!("register {} out of range", reg)));
+                }
+                deltas.push((reg, read_int(reader)?));
+            }
+            let toggled = match read_u8(reader)? {
+                0 => None,
+                _ => Some(read_u32(reader)? as usize)
+            };
+            if let Some(index) = toggled.filter(|index| *index >= program_len) {
+                return Err(invalid_data(&format!("toggled instruction {} out of range", index)));
+            }
+            steps.push(Step { ip, deltas, toggled });
+        }
+        Ok(Trace { program, initial_regs, steps })
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut writer = io::BufWriter::new(std::fs::File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()
+    }
+
+    pub fn load(path: &str) -> io::Result<Trace> {
+        Trace::read_from(&mut io::BufReader::new(std::fs::File::open(path)?))
+    }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+// the value as the narrower integer the format stores it in
+fn fits<T: TryFrom<usize>>(value: usize, what: &str) -> io::Result<T> {
+    T::try_from(value).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, format!("{} {} is too large for a trace", what, value)))
+}
+
+fn write_int<W: Write>(writer: &mut W, value: isize) -> io::Result<()> {
+    writer.write_all(&(value as i64).to_le_bytes())
+}
+
+fn write_value<W: Write>(writer: &mut W, value: &IValue) -> io::Result<()> {
+    match value {
+        IValue::Register(index) => writer.write_all(&[0, *index]),
+        IValue::Literal(intval) => {
+            writer.write_all(&[1])?;
+            write_int(writer, *intval)
+        }
+    }
+}
+
+fn write_instruction<W: Write>(writer: &mut W, instruction: &Instruction) -> io::Result<()> {
+    match instruction {
+        Instruction::Cpy(val1, val2) => {
+            writer.write_all(&[0])?;
+            write_value(writer, val1)?;
+            write_value(writer, val2)
+        },
+        Instruction::Inc(val) => {
+            writer.write_all(&[1])?;
+            write_value(writer, val)
+        },
+        Instruction::Dec(val) => {
+            writer.write_all(&[2])?;
+            write_value(writer, val)
+        },
+        Instruction::Jnz(val1, val2) => {
+            writer.write_all(&[3])?;
+            write_value(writer, val1)?;
+            write_value(writer, val2)
+        },
+        Instruction::Tgl(val) => {
+            writer.write_all(&[4])?;
+            write_value(writer, val)
+        },
+        // day 23 programs cannot contain it, see parse_program
+        Instruction::Out(_) => Err(io::Error::new(io::ErrorKind::InvalidInput, "out is not part of day 23"))
+    }
+}
+
+fn read_u8<R: Read>(reader: &mut R) -> io::Result<u8> {
+    let mut buf = [0u8; 1];
+    reader.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_int<R: Read>(reader: &mut R) -> io::Result<isize> {
+    Ok(read_u64(reader)? as i64 as isize)
+}
+
+fn read_value<R: Read>(reader: &mut R) -> io::Result<IValue> {
+    match read_u8(reader)? {
+        0 => Ok(IValue::Register(read_u8(reader)?)),
+        1 => Ok(IValue::Literal(read_int(reader)?)),
+        tag => Err(invalid_data(&format!("unknown value tag {}", tag)))
+    }
+}
+
+// replaying indexes the registers with these
+fn check_registers(instruction: &Instruction, num_registers: usize) -> io::Result<()> {
+    let operands = match instruction {
+        Instruction::Cpy(val1, val2) | Instruction::Jnz(val1, val2) => vec![val1, val2],
+        Instruction::Inc(val) | Instruction::Dec(val) | Instruction::Tgl(val) | Instruction::Out(val) => vec![val]
+    };
+    for operand in operands {
+        if let IValue::Register(reg) = operand {
+            if *reg as usize >= num_registers {
+                return Err(invalid_data(&format!("register {} out of range", reg)));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_instruction<R: Read>(reader: &mut R) -> io::Result<Instruction> {
+    match read_u8(reader)? {
+        0 => Ok(Instruction::Cpy(read_value(reader)?, read_value(reader)?)),
+        1 => Ok(Instruction::Inc(read_value(reader)?)),
+        2 => Ok(Instruction::Dec(read_value(reader)?)),
+        3 => Ok(Instruction::Jnz(read_value(reader)?, read_value(reader)?)),
+        4 => Ok(Instruction::Tgl(read_value(reader)?)),
+        opcode => Err(invalid_data(&format!("unknown opcode {}", opcode)))
+    }
+}
+
+// steps forward and backward through a trace, reconstructing registers and program
+pub struct Replay<'a> {
+    trace: &'a Trace,
+    // number of steps applied so far
+    position: usize,
+    regs: Vec<isize>,
+    program: Vec<Instruction>,
+    // instructions replaced by toggles, to undo them when stepping backward
+    untoggle: Vec<Option<(usize, Instruction)>>
+}
+
+impl<'a> Replay<'a> {
+    pub fn new(trace: &'a Trace) -> Replay<'a> {
+        Replay {
+            trace,
+            position: 0,
+            regs: trace.initial_regs.clone(),
+            program: trace.program.clone(),
+            untoggle: Vec::new()
+        }
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn regs(&self) -> &[isize] {
+        &self.regs
+    }
+
+    pub fn program(&self) -> &[Instruction] {
+        &self.program
+    }
+
+    // the step that would be applied next
+    pub fn peek(&self) -> Option<&'a Step> {
+        self.trace.steps.get(self.position)
+    }
+
+    // the instruction the next step executes, as toggled so far
+    pub fn instruction(&self) -> Option<Instruction> {
+        self.peek().map(|step| self.program[step.ip])
+    }
+
+    pub fn forward(&mut self) -> Option<&'a Step> {
+        let step = self.trace.steps.get(self.position)?;
+        for (reg, delta) in step.deltas.iter() {
+            self.regs[*reg as usize] = self.regs[*reg as usize].wrapping_add(*delta);
+        }
+        let untoggle = step.toggled.map(|index| {
+            let original = self.program[index];
+            self.program[index] = original.toggle();
+            (index, original)
+        });
+        self.untoggle.push(untoggle);
+        self.position += 1;
+        Some(step)
+    }
+
+    pub fn backward(&mut self) -> Option<&'a Step> {
+        if self.position == 0 {
+            return None;
+        }
+        self.position -= 1;
+        let step = &self.trace.steps[self.position];
+        for (reg, delta) in step.deltas.iter() {
+            self.regs[*reg as usize] = self.regs[*reg as usize].wrapping_sub(*delta);
+        }
+        if let Some((index, original)) = self.untoggle.pop().flatten() {
+            self.program[index] = original;
+        }
+        Some(step)
+    }
+
+    pub fn seek(&mut self, position: usize) {
+        while self.position < position && self.forward().is_some() {}
+        while self.position > position && self.backward().is_some() {}
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Divergence {
+    // index of the first step that differs
+    pub step: usize,
+    // (ip, instruction, registers after the step) of both traces, None if the trace ended
+    pub left: Option<(usize, Instruction, Vec<isize>)>,
+    pub right: Option<(usize, Instruction, Vec<isize>)>
+}
+
+// compares two traces step by step and reports the first step at which
+// the executed instruction or the resulting registers differ
+pub fn diff(left: &Trace, right: &Trace) -> Option<Divergence> {
+    let mut left_replay = Replay::new(left);
+    let mut right_replay = Replay::new(right);
+    loop {
+        let step = left_replay.position();
+        let left_step = left_replay.instruction().zip(left_replay.forward()).map(|(instruction, s)| (s.ip, instruction, left_replay.regs().to_vec()));
+        let right_step = right_replay.instruction().zip(right_replay.forward()).map(|(instruction, s)| (s.ip, instruction, right_replay.regs().to_vec()));
+        if left_step.is_none() && right_step.is_none() {
+            return None;
+        }
+        if left_step != right_step {
+            return Some(Divergence { step, left: left_step, right: right_step });
+        }
+    }
+}
+
+fn print_state(replay: &Replay) {
+    match replay.peek() {
+        Some(step) => println!("step {}: ip {} {:?}\tregs {:?}", replay.position(), step.ip, replay.program()[step.ip], replay.regs()),
+        None => println!("step {}: halted\tregs {:?}", replay.position(), replay.regs())
+    }
+}
+
+// interactive replay on stdin, see the help text for the commands
+pub fn replay_interactive(trace: &Trace) {
+    let mut replay = Replay::new(trace);
+    println!("{} steps recorded. Commands: n [count], p [count], g <step>, l(ist), q(uit)", trace.steps.len());
+    print_state(&replay);
+    for line in io::stdin().lock().lines() {
+        let line = line.expect("Could not read from stdin");
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or("n");
+        let count = words.next().and_then(|w| w.parse::<usize>().ok());
+        match command {
+            "n" => replay.seek(replay.position() + count.unwrap_or(1)),
+            "p" => replay.seek(replay.position().saturating_sub(count.unwrap_or(1))),
+            "g" => replay.seek(count.unwrap_or(0)),
+            "l" => {
+                for (index, instruction) in replay.program().iter().enumerate() {
+                    let marker = if replay.peek().map(|s| s.ip) == Some(index) {">"} else {" "};
+                    println!("{} {:3} {:?}", marker, index, instruction);
+                }
+            },
+            "q" => break,
+            _ => println!("Unknown command")
+        }
+        print_state(&replay);
+    }
+}
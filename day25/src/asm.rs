@@ -0,0 +1,149 @@
+// assembler for an extended assembunny source format:
+//
+//     # multiply a by 3        comments run until the end of the line
+//     .reg a 7                 initial register values
+//     loop: inc b              labels, either in front of an instruction
+//     end:                     or on a line of their own
+//     jnz a loop               jnz and tgl take labels instead of relative offsets
+//
+// Plain puzzle inputs are valid sources as well. The result is the usual Instruction vector,
+// every problem in the source is reported with its line number. Labels always resolve to
+// jumps inside the program, a raw offset pointing outside of it only gets a warning,
+// as the jump may never be taken or be meant to halt the program.
+
+use crate::{Instruction, INSTRUCTIONS, NUM_REGISTERS};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String
+}
+
+impl std::fmt::Display for AsmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Program {
+    pub instructions: Vec<Instruction>,
+    pub regs: [isize; NUM_REGISTERS],
+    pub warnings: Vec<AsmError>
+}
+
+fn register_index(name: &str) -> Option<usize> {
+    match name.as_bytes() {
+        [reg @ b'a'..=b'z'] if ((reg - b'a') as usize) < NUM_REGISTERS => Some((reg - b'a') as usize),
+        _ => None
+    }
+}
+
+fn is_label_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+pub fn assemble(source: &str) -> Result<Program, Vec<AsmError>> {
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+    let mut regs = [0isize; NUM_REGISTERS];
+    let mut labels = HashMap::new();
+    // (line number, instruction text) of every instruction
+    let mut statements = Vec::new();
+
+    // first pass: strip comments, collect labels and directives
+    for (lineno, line) in source.lines().enumerate() {
+        let lineno = lineno + 1;
+        let mut error = |message: String| errors.push(AsmError { line: lineno, message });
+        let mut text = line.split('#').next().unwrap().trim();
+        while let Some((label, rest)) = text.split_once(':') {
+            let label = label.trim();
+            if !is_label_name(label) {
+                error(format!("invalid label name '{}'", label));
+            } else if register_index(label).is_some() {
+                error(format!("label '{}' clashes with a register name", label));
+            } else if labels.insert(label.to_string(), statements.len()).is_some() {
+                error(format!("duplicate label '{}'", label));
+            }
+            text = rest.trim();
+        }
+        if text.is_empty() {
+            continue;
+        }
+        if let Some(directive) = text.strip_prefix('.') {
+            let words = directive.split_whitespace().collect::<Vec<_>>();
+            match words.as_slice() {
+                ["reg", reg, value] => {
+                    match (register_index(reg), value.parse()) {
+                        (Some(index), Ok(value)) => regs[index] = value,
+                        (None, _) => error(format!("unknown register '{}'", reg)),
+                        (_, Err(_)) => error(format!("invalid value '{}'", value))
+                    }
+                },
+                ["reg", ..] => error("usage: .reg <register> <value>".to_string()),
+                _ => error(format!("unknown directive '.{}'", directive))
+            }
+            continue;
+        }
+        statements.push((lineno, text));
+    }
+
+    // second pass: resolve labels to relative offsets and parse the instructions
+    let mut instructions = Vec::new();
+    for (index, (lineno, text)) in statements.iter().enumerate() {
+        let mut error = |message: String| errors.push(AsmError { line: *lineno, message });
+        let mut words = text.split_whitespace().map(|w| w.to_string()).collect::<Vec<_>>();
+        let mut warning = |message: String| warnings.push(AsmError { line: *lineno, message });
+        let name = words[0].to_lowercase();
+        // the operand holding a relative offset
+        let jump_operand = match name.as_str() {
+            "jnz" => Some(2),
+            "tgl" => Some(1),
+            _ => None
+        };
+        let mut resolved = true;
+        let mut labelled = false;
+        for (position, word) in words.iter_mut().enumerate().skip(1) {
+            if let Some(&target) = labels.get(word.as_str()) {
+                if jump_operand == Some(position) {
+                    *word = (target as isize - index as isize).to_string();
+                    labelled = true;
+                } else {
+                    error(format!("label '{}' can only be used as a jump target", word));
+                    resolved = false;
+                }
+            } else if is_label_name(word) && register_index(word).is_none() {
+                error(format!("undefined label '{}'", word));
+                resolved = false;
+            }
+        }
+        if !resolved {
+            continue;
+        }
+        match Instruction::parse(&words.join(" "), NUM_REGISTERS, INSTRUCTIONS) {
+            Ok(instruction) => {
+                match instruction {
+                    Instruction::Jnz(_, crate::IValue::Literal(offset)) if !labelled => {
+                        // jumping right behind the last instruction is the usual way to halt
+                        let target = index as isize + offset;
+                        if target < 0 || target > statements.len() as isize {
+                            warning(format!("jump to {} is out of range 0..={} and halts the program if taken", target, statements.len()));
+                        }
+                    },
+                    _ => {}
+                }
+                instructions.push(instruction);
+            },
+            Err(message) => error(message)
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(Program { instructions, regs, warnings })
+    } else {
+        Err(errors)
+    }
+}
@@ -0,0 +1,43 @@
+// the implementations of this crate for the shared fuzzer, see assembunny::fuzz.
+
+use assembunny::fuzz::{Implementation, Outcome, Regs, STEP_LIMIT};
+use assembunny::{Instruction, InstructionSet};
+
+use crate::Vm;
+
+fn vm(program: &[Instruction], regs: Regs, checked: bool) -> Option<Outcome> {
+    let mut vm = if checked {Vm::new_checked(program, regs)} else {Vm::new(program, regs)};
+    let mut steps = 0;
+    let mut output = Vec::new();
+    while !vm.halted() && steps < STEP_LIMIT {
+        output.extend(vm.step().ok()?);
+        steps += 1;
+    }
+    Some(Outcome { regs: vm.regs, steps, output })
+}
+
+fn unchecked_vm(program: &[Instruction], regs: Regs) -> Option<Outcome> {
+    vm(program, regs, false)
+}
+
+fn checked_vm(program: &[Instruction], regs: Regs) -> Option<Outcome> {
+    vm(program, regs, true)
+}
+
+// the lazy output of the iterator. It only returns once the program halts, so programs
+// that do not halt within the step limit are left to the vm, which also counts the steps
+fn iterator(program: &[Instruction], regs: Regs) -> Option<Outcome> {
+    let stepped = unchecked_vm(program, regs)?;
+    if stepped.steps == STEP_LIMIT {
+        return Some(stepped);
+    }
+    let mut vm = Vm::new(program, regs);
+    let output = vm.by_ref().collect();
+    Some(Outcome { regs: vm.regs, steps: stepped.steps, output })
+}
+
+pub const IMPLEMENTATIONS: &[Implementation] = &[
+    Implementation { name: "day25 vm", instructions: InstructionSet::Output, run: unchecked_vm },
+    Implementation { name: "day25 checked vm", instructions: InstructionSet::Output, run: checked_vm },
+    Implementation { name: "day25 iterator", instructions: InstructionSet::Output, run: iterator }
+];
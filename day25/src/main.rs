@@ -1,159 +1,226 @@
 #![allow(unused)]
 #![allow(dead_code)]
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum IValue {
-    Register(u8),
-    Literal(isize)
-}
+use assembunny::{add, IValue, Instruction, InstructionSet, LoopDetector, Overflow, RunOutcome, NUM_REGISTERS};
 
-impl IValue {
-    fn unwrap(&self) -> isize {
-        match self {
-            IValue::Literal(intval) => *intval as isize,
-            IValue::Register(index) => *index as isize
-        }
-    }
+mod asm;
+mod repl;
+mod solver;
+#[cfg(test)]
+mod fuzz;
 
-    fn is_literal(&self) -> bool {
-        matches!(self, IValue::Literal(_))
-    }
+// the instructions of this day's puzzle
+const INSTRUCTIONS: InstructionSet = InstructionSet::Output;
 
-    fn is_register(&self) -> bool {
-        matches!(self, IValue::Register(_))
-    }
+fn parse_program(lines: &[&str], num_registers: usize) -> Result<Vec<Instruction>, String> {
+    assembunny::parse_program(lines, num_registers, INSTRUCTIONS)
 }
 
-#[derive(Debug, Clone)]
-enum Instruction {
-    Cpy(IValue, IValue),
-    Inc(IValue),
-    Dec(IValue),
-    Jnz(IValue, IValue),
-    Tgl(IValue),
-    Out(IValue)
+// the state of a running assembunny program. Iterating over it executes the program
+// lazily and yields every value sent by out, so callers decide what to do with the signal
+struct Vm<const N: usize> {
+    // the program as modified by tgl so far
+    virtual_instructions: Vec<Instruction>,
+    regs: [isize; N],
+    ip: usize,
+    // trap on overflow instead of wrapping around
+    checked: bool,
+    trap: Option<Overflow>
 }
 
-impl From<&str> for Instruction {
-    fn from(line: &str) -> Self {
-        let (name, args) = line.split_once(' ').unwrap();
-        let arg_to_value = |s: &str| -> IValue {
-            if s.chars().all(|c| c == '-' || c.is_numeric()) {
-                IValue::Literal(s.parse().unwrap())
-            } else {
-                assert!(s.len() == 1);
-                IValue::Register(s.bytes().next().unwrap() as u8 - 'a' as u8)
-            }
-        };
-        let args = args.split_whitespace().map(arg_to_value).collect::<Vec<_>>();
-        match name.to_lowercase().as_str() {
-            "cpy" => Instruction::Cpy(args[0], args[1]),
-            "inc" => Instruction::Inc(args[0]),
-            "dec" => Instruction::Dec(args[0]),
-            "jnz" => Instruction::Jnz(args[0], args[1]),
-            "tgl" => Instruction::Tgl(args[0]),
-            "out" => Instruction::Out(args[0]),
-            _ => panic!("Unknown instruction")
+impl<const N: usize> Vm<N> {
+    fn new(instructions: &[Instruction], regs: [isize; N]) -> Vm<N> {
+        Vm {
+            virtual_instructions: instructions.to_vec(),
+            regs,
+            ip: 0,
+            checked: false,
+            trap: None
         }
     }
-}
 
-impl Instruction {
-    fn toggle(&self) -> Instruction {
-        match *self {
-            Instruction::Inc(val) => Instruction::Dec(val),
-            Instruction::Dec(val) | Instruction::Tgl(val) | Instruction::Out(val) => Instruction::Inc(val),
-            Instruction::Cpy(val1, val2) => Instruction::Jnz(val1, val2),
-            Instruction::Jnz(val1, val2) => Instruction::Cpy(val1, val2)
-        }
+    fn new_checked(instructions: &[Instruction], regs: [isize; N]) -> Vm<N> {
+        Vm { checked: true, ..Vm::new(instructions, regs) }
     }
-}
 
-fn run(instructions: &[Instruction], regs: [isize; 4]) -> [isize; 4] {
-    let mut virtual_instructions = instructions.to_vec();
-    let mut toggled = vec![false; virtual_instructions.len()];
-    let mut regs = regs;
-    let mut ip = 0;
-    let resolve = |val: &IValue, regs: &[isize; 4]| -> isize {
+    fn halted(&self) -> bool {
+        self.ip >= self.virtual_instructions.len() || self.trap.is_some()
+    }
+
+    fn resolve(&self, val: &IValue) -> isize {
         match val {
             IValue::Literal(intval) => *intval,
-            IValue::Register(index) => regs[*index as usize]
-        }
-    };
-    let mut expected_signal = 0;
-    let mut num_signals = 0;
-    while (ip < virtual_instructions.len()) {
-        // we naively assume that if it sends the correct sequence for a length of 128,
-        // it will stay that way
-        if num_signals > 128 {
-            regs[0] = 1;
-            return regs;
+            IValue::Register(index) => self.regs[*index as usize]
         }
-        let instruction = &virtual_instructions[ip];
-        ip += 1;
+    }
+
+    // executes a single instruction, returns the signal if it was an out.
+    // On overflow, ip keeps pointing at the offending instruction
+    fn step(&mut self) -> Result<Option<isize>, Overflow> {
+        let instruction = self.virtual_instructions[self.ip];
+        let overflow = Overflow { ip: self.ip, instruction };
+        let checked = self.checked;
+        let mut next_ip = self.ip + 1;
         match instruction {
             Instruction::Cpy(src, dest) => {
                 if dest.is_register() {
-                    let srcval = resolve(src, &regs);
-                    regs[dest.unwrap() as usize] = srcval;
+                    self.regs[dest.unwrap() as usize] = self.resolve(&src);
                 }
             },
             Instruction::Inc(dest) => {
                 if dest.is_register() {
-                    regs[dest.unwrap() as usize] += 1;
+                    let dest = dest.unwrap() as usize;
+                    self.regs[dest] = add(self.regs[dest], 1, checked).ok_or(overflow)?;
                 }
             },
             Instruction::Dec(dest) => {
                 if dest.is_register() {
-                    regs[dest.unwrap() as usize] -= 1;
+                    let dest = dest.unwrap() as usize;
+                    self.regs[dest] = add(self.regs[dest], -1, checked).ok_or(overflow)?;
                 }
             },
             Instruction::Jnz(src, jmprange) => {
-                let srcval = resolve(src, &regs);
-                if srcval != 0 {
-                    ip = (ip as isize + resolve(jmprange, &regs) - 1) as usize;
+                if self.resolve(&src) != 0 {
+                    next_ip = add(self.ip as isize, self.resolve(&jmprange), checked).ok_or(overflow)? as usize;
                 }
             },
             Instruction::Out(val) => {
-                let signal = resolve(val, &regs);
-                if signal != expected_signal {
-                    regs[0] = 0;
-                    return regs;
-                }
-                expected_signal = if expected_signal == 0 {1} else {0};
-                num_signals += 1;
-            }
+                self.ip = next_ip;
+                return Ok(Some(self.resolve(&val)));
+            },
             Instruction::Tgl(dest) => {
-                let index = ip as isize + resolve(dest, &regs) - 1;
-                if index >= 0 && (index as usize) < virtual_instructions.len() {
+                let index = add(self.ip as isize, self.resolve(&dest), checked).ok_or(overflow)?;
+                if index >= 0 && (index as usize) < self.virtual_instructions.len() {
                     let index = index as usize;
-                    // TODO: It is unclear whether we want to actually toggle like this,
-                    // or just reapply the toggling rule as if it weren't toggled already
-                    if toggled[index] {
-                        virtual_instructions[index] = instructions[index].clone();
-                        toggled[index] = false;
-                    } else {
-                        virtual_instructions[index as usize] = virtual_instructions[index as usize].toggle();
-                        toggled[index] = true;
-                    }
+                    self.virtual_instructions[index] = self.virtual_instructions[index].toggle();
                 }
             }
         }
+        self.ip = next_ip;
+        Ok(None)
+    }
+
+    // runs until the next out and returns its value. None if the program halts, traps,
+    // provably loops without sending anything or needs more than budget steps for it
+    fn next_signal(&mut self, budget: usize) -> Option<isize> {
+        let mut detector = LoopDetector::new();
+        for _ in 0..budget {
+            if self.halted() {
+                return None;
+            }
+            match self.step() {
+                Ok(Some(signal)) => return Some(signal),
+                Ok(None) if detector.looped(self.ip, &self.regs, &self.virtual_instructions) => return None,
+                Ok(None) => {},
+                Err(overflow) => self.trap = Some(overflow)
+            }
+        }
+        None
     }
-    regs
 }
 
-fn part1(lines: &Vec<&str>) -> Option<usize> {
-    let instructions = lines.iter().map(|l| Instruction::from(*l)).collect::<Vec<_>>();
-    for i in (0..) {
-        let mut regs = [0isize; 4];
-        regs[0] = i;
-        let result = run(&instructions, regs);
-        if result[0] == 1 {
-            return Some(i as usize);
+// the puzzle inputs need a few ten thousand steps between two signals,
+// a program that needs more is not sending a clock signal
+const SIGNAL_BUDGET: usize = 1_000_000;
+
+impl<const N: usize> Iterator for Vm<N> {
+    type Item = isize;
+
+    fn next(&mut self) -> Option<isize> {
+        while !self.halted() {
+            match self.step() {
+                Ok(Some(signal)) => return Some(signal),
+                Ok(None) => {},
+                Err(overflow) => self.trap = Some(overflow)
+            }
         }
+        None
     }
-    None
+}
+
+// runs the program until it halts, ignoring its output
+fn run<const N: usize>(instructions: &[Instruction], regs: [isize; N]) -> [isize; N] {
+    let mut vm = Vm::new(instructions, regs);
+    vm.by_ref().for_each(drop);
+    vm.regs
+}
+
+// like run, but traps on overflow with the offending ip and (possibly toggled) instruction
+fn run_checked<const N: usize>(instructions: &[Instruction], regs: [isize; N]) -> Result<[isize; N], Overflow> {
+    let mut vm = Vm::new_checked(instructions, regs);
+    vm.by_ref().for_each(drop);
+    match vm.trap {
+        Some(overflow) => Err(overflow),
+        None => Ok(vm.regs)
+    }
+}
+
+// like run, but gives up once the program needs more than budget steps
+// or provably runs forever
+fn run_with_budget<const N: usize>(instructions: &[Instruction], regs: [isize; N], budget: usize) -> RunOutcome<N> {
+    let mut vm = Vm::new(instructions, regs);
+    let mut detector = LoopDetector::new();
+    let mut steps = 0;
+    while !vm.halted() {
+        if steps == budget {
+            return RunOutcome::BudgetExceeded(vm.regs);
+        }
+        vm.step().unwrap_or_else(|_| unreachable!("unchecked execution never traps"));
+        steps += 1;
+        if detector.looped(vm.ip, &vm.regs, &vm.virtual_instructions) {
+            return RunOutcome::Looped(vm.regs);
+        }
+    }
+    RunOutcome::Halted(vm.regs)
+}
+
+fn expected_signal(index: usize) -> isize {
+    (index % 2) as isize
+}
+
+// we naively assume that if it sends the correct sequence for a length of 128,
+// it will stay that way
+fn looks_like_clock_signal<const N: usize>(instructions: &[Instruction], regs: [isize; N]) -> bool {
+    let mut vm = Vm::new(instructions, regs);
+    (0..=128).all(|i| vm.next() == Some(expected_signal(i)))
+}
+
+// exact variant of looks_like_clock_signal: instead of trusting 128 correct signals,
+// we snapshot the whole VM state every time out fires. The machine is deterministic,
+// so if a state repeats while expecting the same signal, the output is proven to cycle
+// through the same (correct) sequence forever. A wrong signal, halting, overflowing or
+// not sending the next signal within SIGNAL_BUDGET steps rejects the input.
+fn produces_clock_signal<const N: usize>(instructions: &[Instruction], regs: [isize; N]) -> bool {
+    use std::collections::HashSet;
+    let mut vm = Vm::new_checked(instructions, regs);
+    let mut seen = HashSet::new();
+    let mut index = 0;
+    while let Some(signal) = vm.next_signal(SIGNAL_BUDGET) {
+        if signal != expected_signal(index) {
+            return false;
+        }
+        index += 1;
+        // ip already points past the out, the toggle state is part of the program
+        if !seen.insert((vm.regs, vm.ip, vm.virtual_instructions.clone(), expected_signal(index))) {
+            return true;
+        }
+    }
+    false
+}
+
+// the brute force only tries a up to this. The puzzle inputs' answers are a few hundred,
+// as the seed is a + (a few thousand) and the next 0b1010...10 above that is close by
+const MAX_BRUTE_FORCE_A: isize = 1 << 16;
+
+fn part1(lines: &Vec<&str>) -> Option<usize> {
+    let instructions = parse_program(lines, NUM_REGISTERS).unwrap_or_else(|err| panic!("{}", err));
+    if let Some(a) = solver::solve(&instructions) {
+        return Some(a);
+    }
+    // the program does not look like we expect, so fall back to trying every a
+    (0..=MAX_BRUTE_FORCE_A)
+        .find(|a| produces_clock_signal(&instructions, [*a, 0, 0, 0]))
+        .map(|a| a as usize)
 }
 
 
@@ -166,8 +233,29 @@ fn main() {
     use std::env;
     use std::time::Instant;
     let args: Vec<String> =  env::args().collect();
+    if args.get(1).map(|a| a.as_str()) == Some("--repl") {
+        let program = match args.get(2) {
+            Some(path) => {
+                let contents = fs::read_to_string(path).expect("Could not read in file");
+                let program = asm::assemble(&contents).unwrap_or_else(|errors| {
+                    for error in errors {
+                        println!("{}", error);
+                    }
+                    std::process::exit(1);
+                });
+                for warning in program.warnings.iter() {
+                    println!("warning: {}", warning);
+                }
+                program
+            },
+            None => asm::Program { instructions: Vec::new(), regs: [0isize; NUM_REGISTERS], warnings: Vec::new() }
+        };
+        repl::run_interactive(program);
+        return;
+    }
     let infile = args.get(1).unwrap_or_else(|| {
         println!("Usage: {} <puzzle input>", args[0]);
+        println!("       {} --repl [program]", args[0]);
         std::process::exit(1);
     });
 
@@ -202,12 +290,321 @@ dec a";
 
     #[test]
     fn test_part1() {
-        let lines: Vec<&str> = TESTINPUT.lines().collect();
-        let instructions = lines.iter().map(|l| Instruction::from(*l)).collect::<Vec<_>>();
+        let instructions = parse(TESTINPUT);
         let regs = run(&instructions, [0isize; 4]);
         assert_eq!(3, regs[0]);
     }
 
+    fn parse(input: &str) -> Vec<Instruction> {
+        parse_program(&input.lines().collect::<Vec<_>>(), NUM_REGISTERS).unwrap()
+    }
+
+    #[test]
+    fn test_clock_signal_proven() {
+        // outputs 0,1 forever iff a is zero
+        let instructions = parse("jnz a 4\nout 0\nout 1\njnz 1 -2\nout 1");
+        assert!(produces_clock_signal(&instructions, [0, 0, 0, 0]));
+        assert!(!produces_clock_signal(&instructions, [1, 0, 0, 0]));
+    }
+
+    #[test]
+    fn test_clock_signal_halting_is_rejected() {
+        let instructions = parse("out 0\nout 1");
+        assert!(!produces_clock_signal(&instructions, [0isize; 4]));
+    }
+
+    #[test]
+    fn test_clock_signal_late_divergence() {
+        // 200 correct signals, then a wrong one: fools the heuristic, but not the exact check
+        let instructions = parse("cpy 100 b\nout 0\nout 1\ndec b\njnz b -3\nout 1");
+        assert!(looks_like_clock_signal(&instructions, [0isize; 4]));
+        assert!(!produces_clock_signal(&instructions, [0isize; 4]));
+    }
+
+    #[test]
+    fn test_clock_signal_silent_loop_is_rejected() {
+        // sends 0 and 1 once, then spins without output
+        let instructions = parse("out 0\nout 1\njnz 1 0");
+        assert!(!produces_clock_signal(&instructions, [0isize; 4]));
+        // the same, but counting up, so no state ever repeats
+        let instructions = parse("out 0\nout 1\ninc a\njnz 1 -1");
+        assert!(!produces_clock_signal(&instructions, [0isize; 4]));
+        // and overflowing while counting
+        assert!(!produces_clock_signal(&instructions, [isize::MAX - 10, 0, 0, 0]));
+    }
+
+    #[test]
+    fn test_brute_force_is_bounded() {
+        // no clock signal for any a, the solver gives up and so does the brute force
+        let lines = vec!["out a", "jnz 1 -1"];
+        assert_eq!(None, solver::solve(&parse("out a\njnz 1 -1")));
+        assert_eq!(None, part1(&lines));
+    }
+
+    #[test]
+    fn test_run_with_budget() {
+        assembunny::fuzz::check_budget(run_with_budget);
+        // the clock never halts, but the output keeps its state changing until it repeats
+        let instructions = parse(CLOCKINPUT);
+        assert!(matches!(run_with_budget(&instructions, [198, 0, 0, 0], usize::MAX), RunOutcome::Looped(_)));
+    }
+
+    #[test]
+    fn test_vm_streams_output() {
+        // counts down from 3, sending every value
+        let instructions = parse("cpy 3 a\nout a\ndec a\njnz a -2\nout 100");
+        let signal: Vec<isize> = Vm::new(&instructions, [0isize; 4]).collect();
+        assert_eq!(vec![3, 2, 1, 100], signal);
+    }
+
+    #[test]
+    fn test_vm_output_is_lazy() {
+        // never halts, so taking a prefix must not run the whole program
+        let instructions = parse("out a\ninc a\njnz 1 -2");
+        let signal: String = Vm::new(&instructions, [72, 0, 0, 0])
+            .take(2)
+            .map(|c| c as u8 as char)
+            .collect();
+        assert_eq!("HI", signal);
+    }
+
+    #[test]
+    fn test_parse_output_instructions() {
+        assert_eq!(Ok(Instruction::Out(IValue::Register(3))), Instruction::parse("out d", 4, INSTRUCTIONS));
+        assert_eq!(Ok(Instruction::Out(IValue::Literal(1))), Instruction::parse("out 1", 4, INSTRUCTIONS));
+        assert_eq!(vec![Instruction::Tgl(IValue::Register(0)), Instruction::Cpy(IValue::Literal(1), IValue::Literal(2))],
+            parse_program(&["tgl a", "cpy 1 2"], 4).unwrap());
+    }
+
+    #[test]
+    fn test_more_registers() {
+        let instructions = parse_program(&["cpy 2 e", "out e", "dec e", "jnz e -2"], 5).unwrap();
+        let signal: Vec<isize> = Vm::new(&instructions, [0isize; 5]).collect();
+        assert_eq!(vec![2, 1], signal);
+    }
+
+    #[test]
+    fn test_checked_overflow() {
+        let instructions = parse("out a\ninc a\nout a");
+        let mut vm = Vm::new_checked(&instructions, [isize::MAX, 0, 0, 0]);
+        assert_eq!(Some(isize::MAX), vm.next());
+        assert_eq!(None, vm.next());
+        assert_eq!(Some(Overflow { ip: 1, instruction: Instruction::Inc(IValue::Register(0)) }), vm.trap);
+        assert_eq!(1, vm.ip);
+        assert!(run_checked(&instructions, [isize::MAX, 0, 0, 0]).is_err());
+        assert_eq!(Ok([0, 0, 0, 0]), run_checked(&instructions, [-1, 0, 0, 0]));
+    }
+
+    // same structure as the puzzle inputs: emits the digits of a + 4 * 633
+    static CLOCKINPUT: &str = "cpy a d
+cpy 4 c
+cpy 633 b
+inc d
+dec b
+jnz b -2
+dec c
+jnz c -5
+cpy d a
+jnz 0 0
+cpy a b
+cpy 0 a
+cpy 2 c
+jnz b 2
+jnz 1 6
+dec b
+dec c
+jnz c -4
+inc a
+jnz 1 -7
+cpy 2 b
+jnz c 2
+jnz 1 4
+dec b
+dec c
+jnz 1 -4
+jnz 0 0
+out b
+jnz a -19
+jnz 1 -21";
+
+    #[test]
+    fn test_analyse_prelude() {
+        let instructions = parse(CLOCKINPUT);
+        let prelude = solver::analyse_prelude(&instructions).unwrap();
+        assert_eq!(13, prelude.ip);
+        assert_eq!(solver::Affine { coef: 1, offset: 2532 }, prelude.seed);
+        assert_eq!(solver::Affine { coef: 1, offset: 2532 }, prelude.regs[3]);
+    }
+
+    #[test]
+    fn test_solver_matches_brute_force() {
+        let lines: Vec<&str> = CLOCKINPUT.lines().collect();
+        let instructions = parse(CLOCKINPUT);
+        // 2730 = 0b101010101010 is the first pattern above 2532
+        assert_eq!(Some(198), solver::solve(&instructions));
+        let brute_force = (0..=MAX_BRUTE_FORCE_A).find(|a| produces_clock_signal(&instructions, [*a, 0, 0, 0]));
+        assert_eq!(Some(198), brute_force);
+        assert_eq!(Some(198), part1(&lines));
+    }
+
+    #[test]
+    fn test_solver_probes_are_bounded() {
+        // counts b up forever without sending anything once a is not zero
+        let instructions = parse("jnz a 2\nout 0\ninc b\njnz 1 -1");
+        assert!(solver::analyse_prelude(&instructions).is_some());
+        assert_eq!(None, solver::solve(&instructions));
+        // overflows right after the branch
+        let instructions = parse("jnz a 2\nout 0\ncpy 9223372036854775807 b\ninc b\nout 0");
+        assert_eq!(None, solver::solve(&instructions));
+    }
+
+    #[test]
+    fn test_solver_rejects_other_programs() {
+        // branches on a right away, but is no bit emitter
+        let instructions = parse("jnz a 4\nout 0\nout 1\njnz 1 -2\nout 1");
+        assert!(solver::analyse_prelude(&instructions).is_some());
+        assert_eq!(None, solver::solve(&instructions));
+        // sends output before looking at a
+        assert_eq!(None, solver::analyse_prelude(&parse("out 0\njnz a 2")));
+        let lines: Vec<&str> = "jnz a 4\nout 0\nout 1\njnz 1 -2\nout 1".lines().collect();
+        assert_eq!(Some(0), part1(&lines));
+    }
+
+    #[test]
+    fn test_repl_session() {
+        let mut repl = repl::Repl::new(Vec::new(), [0isize; 4]);
+        assert!(repl.execute("cpy 3 a").is_ok());
+        assert!(repl.execute("out a").is_ok());
+        assert!(repl.execute("dec a").is_ok());
+        assert!(repl.execute("jnz a -2").is_ok());
+        assert!(repl.execute("list").unwrap().starts_with(">   0 Cpy(Literal(3), Register(0))"));
+        assert_eq!("2 step(s)\nout: 3\na=3 b=0 c=0 d=0 ip=2", repl.execute("step 2").unwrap());
+        assert_eq!("8 step(s)\nout: 2,1\na=0 b=0 c=0 d=0 ip=4 (halted)", repl.execute("run").unwrap());
+        assert_eq!("a=0 b=0 c=0 d=0 ip=0", repl.execute("reset").unwrap());
+        assert_eq!("a=0 b=5 c=0 d=0 ip=0", repl.execute("set b 5").unwrap());
+        assert_eq!("4 Inc(Register(1))", repl.execute("inc b").unwrap().trim());
+    }
+
+    #[test]
+    fn test_repl_reset_keeps_initial_registers() {
+        let path = std::env::temp_dir().join(format!("day25-repl-reset-{}.asm", std::process::id()));
+        std::fs::write(&path, ".reg a 7\n.reg c 2\ninc a\n").unwrap();
+        let mut repl = repl::Repl::new(Vec::new(), [0isize; 4]);
+        let loaded = repl.execute(&format!("load {}", path.display()));
+        std::fs::remove_file(&path).unwrap();
+        assert!(loaded.is_ok());
+        repl.execute("step").unwrap();
+        assert_eq!("a=9 b=0 c=2 d=0 ip=1 (halted)", repl.execute("set a 9").unwrap());
+        assert_eq!("a=7 b=0 c=2 d=0 ip=0", repl.execute("reset").unwrap());
+        let mut repl = repl::Repl::new(parse("inc b"), [1, 2, 3, 4]);
+        repl.execute("step").unwrap();
+        assert_eq!("a=1 b=2 c=3 d=4 ip=0", repl.execute("reset").unwrap());
+    }
+
+    #[test]
+    fn test_repl_traps_on_overflow() {
+        let mut repl = repl::Repl::new(parse("inc a\nout a"), [isize::MAX, 0, 0, 0]);
+        let trapped = format!("a={} b=0 c=0 d=0 ip=0 (trapped: arithmetic overflow at ip 0 (Inc(Register(0))), reset to start over)", isize::MAX);
+        assert_eq!(format!("0 step(s)\n{}", trapped), repl.execute("run").unwrap());
+        assert_eq!(trapped, repl.execute("regs").unwrap());
+        assert_eq!(format!("0 step(s)\n{}", trapped), repl.execute("step").unwrap());
+        assert_eq!(format!("a={} b=0 c=0 d=0 ip=0", isize::MAX), repl.execute("reset").unwrap());
+        repl.execute("set a 1").unwrap();
+        assert_eq!("2 step(s)\nout: 2\na=2 b=0 c=0 d=0 ip=2 (halted)", repl.execute("run").unwrap());
+    }
+
+    #[test]
+    fn test_repl_errors() {
+        let mut repl = repl::Repl::new(Vec::new(), [0isize; 4]);
+        assert!(repl.execute("inc e").unwrap_err().contains("unknown register"));
+        assert!(repl.execute("set e 1").is_err());
+        assert!(repl.execute("set a x").is_err());
+        assert!(repl.execute("step x").is_err());
+        assert!(repl.execute("load /nonexistent").is_err());
+        assert!(repl.execute("frobnicate").is_err());
+        // nothing to do for an empty program
+        assert_eq!("0 step(s)\na=0 b=0 c=0 d=0 ip=0 (halted)", repl.execute("run").unwrap());
+    }
+
+    #[test]
+    fn test_repl_run_stops_on_loops() {
+        let mut repl = repl::Repl::new(parse("out 1\njnz 1 0"), [0isize; 4]);
+        let report = repl.execute("run").unwrap();
+        assert!(report.starts_with("2 step(s)\nout: 1"));
+        assert!(report.ends_with("never halts"));
+        assert_eq!("a=0 b=0 c=0 d=0 ip=1", repl.execute("regs").unwrap());
+    }
+
+    #[test]
+    fn test_assemble_labels() {
+        let source = "# counts a down to zero, sending every value
+.reg a 3
+loop: out a   # send
+      dec a
+      jnz a loop
+      jnz 1 end
+      out 100
+end:";
+        let program = asm::assemble(source).unwrap();
+        assert_eq!(parse("out a\ndec a\njnz a -2\njnz 1 2\nout 100"), program.instructions);
+        assert_eq!([3, 0, 0, 0], program.regs);
+        let signal: Vec<isize> = Vm::new(&program.instructions, program.regs).collect();
+        assert_eq!(vec![3, 2, 1], signal);
+    }
+
+    #[test]
+    fn test_assemble_plain_input() {
+        let program = asm::assemble(CLOCKINPUT).unwrap();
+        assert_eq!(parse(CLOCKINPUT), program.instructions);
+    }
+
+    #[test]
+    fn test_assemble_toggle_label() {
+        let program = asm::assemble("tgl target\n\ninc b\ntarget: inc a").unwrap();
+        assert_eq!(Instruction::Tgl(IValue::Literal(2)), program.instructions[0]);
+        assert_eq!([-1, 1, 0, 0], run(&program.instructions, program.regs));
+    }
+
+    #[test]
+    fn test_assemble_diagnostics() {
+        let errors = asm::assemble("jnz a nowhere\nloop: inc a\nloop: dec a\ncpy loop b\na: inc a\n.reg e 1\n.org 5").unwrap_err();
+        let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(vec![
+            "line 3: duplicate label 'loop'",
+            "line 5: label 'a' clashes with a register name",
+            "line 6: unknown register 'e'",
+            "line 7: unknown directive '.org 5'",
+            "line 1: undefined label 'nowhere'",
+            "line 4: label 'loop' can only be used as a jump target",
+        ], messages);
+    }
+
+    #[test]
+    fn test_assemble_warns_about_raw_jumps_out_of_range() {
+        // never taken, or taken to halt the program
+        let program = asm::assemble("jnz 0 99\ninc a\njnz a -5\njnz 1 1").unwrap();
+        assert_eq!(parse("jnz 0 99\ninc a\njnz a -5\njnz 1 1"), program.instructions);
+        let warnings: Vec<String> = program.warnings.iter().map(|w| w.to_string()).collect();
+        assert_eq!(vec![
+            "line 1: jump to 99 is out of range 0..=4 and halts the program if taken",
+            "line 3: jump to -3 is out of range 0..=4 and halts the program if taken",
+        ], warnings);
+        assert_eq!([1, 0, 0, 0], run(&program.instructions, program.regs));
+        assert!(asm::assemble(CLOCKINPUT).unwrap().warnings.is_empty());
+    }
+
+    #[test]
+    fn test_fuzz_implementations_agree() {
+        assembunny::fuzz::check(fuzz::IMPLEMENTATIONS, 25, 3000);
+    }
+
+    #[test]
+    fn test_fuzz_all_days_agree() {
+        // every program runs on the implementations of all days that understand it
+        let implementations = [day12::fuzz::IMPLEMENTATIONS, day23::fuzz::IMPLEMENTATIONS, fuzz::IMPLEMENTATIONS].concat();
+        assembunny::fuzz::check(&implementations, 2016, 3000);
+    }
+
     #[test]
     #[ignore]
     fn test_part2() {
@@ -0,0 +1,177 @@
+// interactive assembunny shell, understands every instruction of days 12, 23 and 25.
+// Instructions typed at the prompt are appended to the program, everything else is a command.
+
+use assembunny::LoopDetector;
+
+use crate::asm;
+use crate::{Instruction, Vm, INSTRUCTIONS, NUM_REGISTERS};
+use std::io::{self, BufRead, Write};
+
+// run without a step count stops after this many steps, in case the program never halts
+const RUN_STEP_LIMIT: usize = 100_000_000;
+
+const HELP: &str = "Commands:
+  <instruction>       append an instruction, e.g. cpy 41 a
+  load <file>         replace the program with an assembly file (labels, comments, .reg)
+  set <reg> <value>   set a register, e.g. set a 7
+  step [n]            execute n instructions (default 1)
+  run                 execute until the program halts or loops forever
+  regs                show registers and ip
+  list                show the program, as modified by tgl
+  reset               reset registers to their initial values, ip and toggles
+  clear               remove all instructions
+  help                show this text
+  quit                leave";
+
+pub struct Repl {
+    program: Vec<Instruction>,
+    // as given to new or by the .reg directives of load, reset goes back to them
+    initial_regs: [isize; NUM_REGISTERS],
+    vm: Vm<NUM_REGISTERS>
+}
+
+impl Repl {
+    pub fn new(program: Vec<Instruction>, regs: [isize; NUM_REGISTERS]) -> Repl {
+        // a trap is shown like halting instead of panicking or wrapping around
+        let vm = Vm::new_checked(&program, regs);
+        Repl { program, initial_regs: regs, vm }
+    }
+
+    fn regs_line(&self) -> String {
+        let regs = self.vm.regs.iter()
+            .enumerate()
+            .map(|(index, value)| format!("{}={}", (b'a' + index as u8) as char, value))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let state = match self.vm.trap {
+            Some(overflow) => format!(" (trapped: {}, reset to start over)", overflow),
+            None if self.vm.halted() => " (halted)".to_string(),
+            None => String::new()
+        };
+        format!("{} ip={}{}", regs, self.vm.ip, state)
+    }
+
+    fn listing(&self) -> String {
+        self.vm.virtual_instructions.iter()
+            .enumerate()
+            .map(|(index, instruction)| {
+                let marker = if index == self.vm.ip {">"} else {" "};
+                format!("{} {:3} {:?}", marker, index, instruction)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    // executes up to max_steps instructions, reports the output and the new state.
+    // With a detector, also stops once the program is caught in a loop, the flag tells if it was
+    fn execute_steps(&mut self, max_steps: usize, mut detector: Option<LoopDetector<Instruction, NUM_REGISTERS>>) -> (String, bool) {
+        let mut output = Vec::new();
+        let mut steps = 0;
+        let mut looped = false;
+        while steps < max_steps && !self.vm.halted() {
+            match self.vm.step() {
+                Ok(Some(signal)) => output.push(signal.to_string()),
+                Ok(None) => {},
+                Err(overflow) => {
+                    // the offending instruction was not executed
+                    self.vm.trap = Some(overflow);
+                    break;
+                }
+            }
+            steps += 1;
+            if detector.as_mut().is_some_and(|detector| detector.looped(self.vm.ip, &self.vm.regs, &self.vm.virtual_instructions)) {
+                looped = true;
+                break;
+            }
+        }
+        let mut report = format!("{} step(s)", steps);
+        if !output.is_empty() {
+            report.push_str(&format!("\nout: {}", output.join(",")));
+        }
+        report.push('\n');
+        report.push_str(&self.regs_line());
+        (report, looped)
+    }
+
+    // handles one line of input, returns the text to show or an error message
+    pub fn execute(&mut self, line: &str) -> Result<String, String> {
+        let line = line.trim();
+        let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let rest = rest.trim();
+        match command {
+            "" => Ok(String::new()),
+            "help" => Ok(HELP.to_string()),
+            "regs" => Ok(self.regs_line()),
+            "list" => Ok(self.listing()),
+            "load" => {
+                let contents = std::fs::read_to_string(rest).map_err(|err| format!("could not read {}: {}", rest, err))?;
+                let program = asm::assemble(&contents).map_err(|errors| {
+                    errors.iter().map(|e| e.to_string()).collect::<Vec<_>>().join("\n")
+                })?;
+                let warnings = program.warnings.iter().map(|w| format!("\nwarning: {}", w)).collect::<String>();
+                *self = Repl::new(program.instructions, program.regs);
+                Ok(format!("loaded {} instructions{}", self.program.len(), warnings))
+            },
+            "set" => {
+                let (reg, value) = rest.split_once(' ').ok_or("usage: set <reg> <value>")?;
+                let index = match reg.as_bytes() {
+                    [reg @ b'a'..=b'z'] if ((reg - b'a') as usize) < NUM_REGISTERS => (reg - b'a') as usize,
+                    _ => return Err(format!("unknown register '{}'", reg))
+                };
+                self.vm.regs[index] = value.trim().parse().map_err(|_| format!("invalid value '{}'", value.trim()))?;
+                Ok(self.regs_line())
+            },
+            "step" => {
+                let count = if rest.is_empty() {1} else {rest.parse().map_err(|_| format!("invalid step count '{}'", rest))?};
+                Ok(self.execute_steps(count, None).0)
+            },
+            "run" => {
+                let (report, looped) = self.execute_steps(RUN_STEP_LIMIT, Some(LoopDetector::new()));
+                if self.vm.halted() {
+                    Ok(report)
+                } else if looped {
+                    Ok(format!("{}\nstopped, the program repeats a previous state and never halts", report))
+                } else {
+                    Ok(format!("{}\nstopped after {} steps, the program may not halt", report, RUN_STEP_LIMIT))
+                }
+            },
+            "reset" => {
+                *self = Repl::new(std::mem::take(&mut self.program), self.initial_regs);
+                Ok(self.regs_line())
+            },
+            "clear" => {
+                *self = Repl::new(Vec::new(), [0isize; NUM_REGISTERS]);
+                Ok("program cleared".to_string())
+            },
+            _ => {
+                // anything else has to be an instruction
+                let instruction = Instruction::parse(line, NUM_REGISTERS, INSTRUCTIONS)?;
+                self.program.push(instruction);
+                self.vm.virtual_instructions.push(instruction);
+                Ok(format!("{:3} {:?}", self.program.len() - 1, instruction))
+            }
+        }
+    }
+}
+
+pub fn run_interactive(program: asm::Program) {
+    let mut repl = Repl::new(program.instructions, program.regs);
+    println!("assembunny repl, type help for a list of commands");
+    let stdin = io::stdin();
+    loop {
+        print!("> ");
+        io::stdout().flush().expect("Could not write to stdout");
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).expect("Could not read from stdin") == 0 {
+            break;
+        }
+        if matches!(line.trim(), "quit" | "exit") {
+            break;
+        }
+        match repl.execute(&line) {
+            Ok(text) if text.is_empty() => {},
+            Ok(text) => println!("{}", text),
+            Err(err) => println!("error: {}", err)
+        }
+    }
+}
@@ -0,0 +1,150 @@
+// analytic solver for the clock signal puzzle.
+//
+// Every day25 input has the same shape: a prelude computes seed = a + K with
+// nested counting loops, then an emitter loop sends the binary digits of seed,
+// least significant first, and starts over with the same seed once all digits are out.
+// Instead of trying a = 0, 1, 2, ... we
+//  1. run the prelude with a abstracted as an affine value coef * a + offset,
+//     until the first branch that depends on a,
+//  2. check that the remaining program behaves like the emitter described above,
+//  3. pick the smallest seed 0b10, 0b1010, ... reachable from a >= 0 and
+//     prove the resulting a with the exact cycle check.
+
+use crate::{produces_clock_signal, IValue, Instruction, Vm, NUM_REGISTERS, SIGNAL_BUDGET};
+
+// coef * a + offset, where a is the initial value of register a
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Affine {
+    pub coef: isize,
+    pub offset: isize
+}
+
+impl Affine {
+    fn constant(value: isize) -> Affine {
+        Affine { coef: 0, offset: value }
+    }
+
+    fn as_constant(&self) -> Option<isize> {
+        if self.coef == 0 {Some(self.offset)} else {None}
+    }
+
+    fn eval(&self, a: isize) -> isize {
+        self.coef * a + self.offset
+    }
+}
+
+// the abstract state at the first branch that depends on a
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Prelude {
+    pub ip: usize,
+    pub regs: [Affine; NUM_REGISTERS],
+    // the value the branch depends on
+    pub seed: Affine
+}
+
+// the counting loops of the prelude run a few thousand steps, anything beyond this
+// is not the kind of program we can analyse
+const PRELUDE_STEP_LIMIT: usize = 1_000_000;
+
+// abstract interpretation of the program until the first branch on a value depending on a.
+// Gives up on tgl and out in the prelude, on halting and on non-affine jumps
+pub fn analyse_prelude(instructions: &[Instruction]) -> Option<Prelude> {
+    let mut regs = [Affine::constant(0); NUM_REGISTERS];
+    regs[0] = Affine { coef: 1, offset: 0 };
+    let resolve = |val: &IValue, regs: &[Affine; NUM_REGISTERS]| -> Affine {
+        match val {
+            IValue::Literal(intval) => Affine::constant(*intval),
+            IValue::Register(index) => regs[*index as usize]
+        }
+    };
+    let mut ip = 0;
+    for _ in 0..PRELUDE_STEP_LIMIT {
+        let instruction = instructions.get(ip)?;
+        match instruction {
+            Instruction::Cpy(src, IValue::Register(dest)) => {
+                regs[*dest as usize] = resolve(src, &regs);
+            },
+            Instruction::Inc(IValue::Register(dest)) => {
+                regs[*dest as usize].offset += 1;
+            },
+            Instruction::Dec(IValue::Register(dest)) => {
+                regs[*dest as usize].offset -= 1;
+            },
+            Instruction::Jnz(src, jmprange) => {
+                let cond = resolve(src, &regs);
+                match cond.as_constant() {
+                    None => return Some(Prelude { ip, regs, seed: cond }),
+                    Some(0) => {},
+                    Some(_) => {
+                        let offset = resolve(jmprange, &regs).as_constant()?;
+                        let target = ip as isize + offset;
+                        if target < 0 {
+                            return None;
+                        }
+                        ip = target as usize;
+                        continue;
+                    }
+                }
+            },
+            Instruction::Tgl(_) | Instruction::Out(_) => return None,
+            // invalid instructions are skipped
+            _ => {}
+        }
+        ip += 1;
+    }
+    None
+}
+
+fn binary_digits(value: isize) -> Vec<isize> {
+    let mut digits = Vec::new();
+    let mut value = value;
+    while value > 0 {
+        digits.push(value % 2);
+        value /= 2;
+    }
+    digits
+}
+
+// runs the rest of the program from the analysed branch for a concrete seed.
+// None if it halts, overflows or runs out of SIGNAL_BUDGET before sending count values
+fn emitter_output(instructions: &[Instruction], prelude: &Prelude, seed: isize, count: usize) -> Option<Vec<isize>> {
+    // the seed is coef * a + offset with coef == 1, see solve()
+    let a = seed - prelude.seed.offset;
+    let mut vm = Vm::new_checked(instructions, prelude.regs.map(|reg| reg.eval(a)));
+    vm.ip = prelude.ip;
+    (0..count).map(|_| vm.next_signal(SIGNAL_BUDGET)).collect()
+}
+
+// the emitter sends the digits of seed, least significant first, over and over
+fn is_bit_emitter(instructions: &[Instruction], prelude: &Prelude, seeds: impl Iterator<Item = isize>) -> bool {
+    seeds.into_iter().all(|seed| {
+        let digits = binary_digits(seed);
+        emitter_output(instructions, prelude, seed, 2 * digits.len())
+            .is_some_and(|output| output.chunks(digits.len()).all(|chunk| chunk == digits))
+    })
+}
+
+// smallest a >= 0 whose seed is 0b1010...10, so the digits read 0, 1, 0, 1, ...
+// and there is an even number of them, keeping the phase when the emitter starts over
+pub fn solve(instructions: &[Instruction]) -> Option<usize> {
+    let prelude = analyse_prelude(instructions)?;
+    if prelude.seed.coef != 1 {
+        return None;
+    }
+    let mut pattern: isize = 0b10;
+    while pattern < prelude.seed.offset {
+        pattern = pattern.checked_mul(4)?.checked_add(0b10)?;
+    }
+    let candidate = pattern - prelude.seed.offset;
+    let probes = (1..=64).chain([pattern]);
+    if !is_bit_emitter(instructions, &prelude, probes) {
+        return None;
+    }
+    let mut regs = [0isize; NUM_REGISTERS];
+    regs[0] = candidate;
+    if produces_clock_signal(instructions, regs) {
+        Some(candidate as usize)
+    } else {
+        None
+    }
+}